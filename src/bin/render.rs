@@ -0,0 +1,263 @@
+//! Render one frame of a model or a scene into a png, without a browser.
+//!
+//! ```bash
+//! $ cargo run --target x86_64-unknown-linux-gnu --bin models_player-render -- \
+//!     Yueqin.obj --eye 100,0,0 --fovy 10 -o yueqin.png
+//! ```
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+    use models_player::{
+        main_player::{
+            AnimationPlayer, BloomSettings, Camera, CameraController, LoopMode, NodeContent,
+            NodeTransform, Projection, SsaoSettings, State, Tonemapping, ViewPreset,
+        },
+        requests,
+    };
+
+    #[derive(Parser, Debug)]
+    #[command(about = "Render a snapshot of a model or a scene into a png")]
+    struct Args {
+        /// Model file name inside `static/obj`, or `static/gltf` for `.gltf` and `.glb`
+        #[arg(required_unless_present = "scene")]
+        model: Option<String>,
+
+        /// Scene file name inside `static/scene`, drawn together with the model
+        #[arg(long)]
+        scene: Option<String>,
+
+        /// Equirectangular `.hdr` inside `static/image` lighting the models
+        #[arg(long)]
+        environment: Option<String>,
+
+        /// Directory holding `static/`
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+
+        /// Output png
+        #[arg(short, long, default_value = "snapshot.png")]
+        output: PathBuf,
+
+        /// Camera position, as `x,y,z` [default: the one of the scene, or framing the models]
+        #[arg(long, value_parser = parse_point)]
+        eye: Option<cgmath::Point3<f32>>,
+
+        /// Point the camera looks at, as `x,y,z` [default: the one of the scene, or the middle of the models]
+        #[arg(long, value_parser = parse_point)]
+        target: Option<cgmath::Point3<f32>>,
+
+        /// Vertical field of view in degrees, between 0 and 180 [default: the one of the scene, or 45]
+        #[arg(long, value_parser = parse_fovy)]
+        fovy: Option<f32>,
+
+        /// `perspective` or `orthographic` [default: the one of the scene, or perspective]
+        #[arg(long, value_parser = parse_projection)]
+        projection: Option<Projection>,
+
+        /// Look at the target from `front`, `back`, `left`, `right`, `top`, `bottom` or `isometric`,
+        /// as far from it as the eye
+        #[arg(long, value_parser = parse_view)]
+        view: Option<ViewPreset>,
+
+        #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u32).range(1..))]
+        width: u32,
+
+        #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u32).range(1..))]
+        height: u32,
+
+        /// Seconds into the animations, the first one of the model and all of the scene
+        #[arg(long)]
+        time: Option<f32>,
+
+        /// Multiplier of the colors before tonemapping [default: the one of the scene, or 1]
+        #[arg(long)]
+        exposure: Option<f32>,
+
+        /// `none`, `reinhard` or `aces` [default: the one of the scene, or aces]
+        #[arg(long, value_parser = parse_tonemapping)]
+        tonemapping: Option<Tonemapping>,
+
+        /// Strength of the glow of bright colors, 0 turns it off [default: the one of the scene, or 0.5]
+        #[arg(long)]
+        bloom: Option<f32>,
+
+        /// Samples per pixel, 1 turns multisampling off [default: the one of the scene, or 1]
+        #[arg(long)]
+        msaa: Option<u32>,
+
+        /// Radius of the ambient occlusion in world units, 0 turns it off [default: the one of the scene, or off]
+        #[arg(long)]
+        ssao: Option<f32>,
+
+        /// Smooth the edges with FXAA after tonemapping
+        #[arg(long)]
+        fxaa: bool,
+    }
+
+    fn parse_point(s: &str) -> Result<cgmath::Point3<f32>, String> {
+        let coords = s
+            .split(',')
+            .map(|c| c.trim().parse::<f32>().map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match coords[..] {
+            [x, y, z] => Ok(cgmath::Point3 { x, y, z }),
+            _ => Err(format!("expect `x,y,z`, got `{}`", s)),
+        }
+    }
+
+    fn parse_fovy(s: &str) -> Result<f32, String> {
+        let fovy = s.parse::<f32>().map_err(|e| e.to_string())?;
+        if fovy > 0.0 && fovy < 180.0 {
+            Ok(fovy)
+        } else {
+            Err(format!("expect degrees between 0 and 180, got `{}`", s))
+        }
+    }
+
+    fn parse_tonemapping(s: &str) -> Result<Tonemapping, String> {
+        match s {
+            "none" => Ok(Tonemapping::None),
+            "reinhard" => Ok(Tonemapping::Reinhard),
+            "aces" => Ok(Tonemapping::Aces),
+            _ => Err(format!("expect `none`, `reinhard` or `aces`, got `{}`", s)),
+        }
+    }
+
+    fn parse_projection(s: &str) -> Result<Projection, String> {
+        match s {
+            "perspective" => Ok(Projection::Perspective),
+            "orthographic" => Ok(Projection::Orthographic),
+            _ => Err(format!(
+                "expect `perspective` or `orthographic`, got `{}`",
+                s
+            )),
+        }
+    }
+
+    fn parse_view(s: &str) -> Result<ViewPreset, String> {
+        match s {
+            "front" => Ok(ViewPreset::Front),
+            "back" => Ok(ViewPreset::Back),
+            "left" => Ok(ViewPreset::Left),
+            "right" => Ok(ViewPreset::Right),
+            "top" => Ok(ViewPreset::Top),
+            "bottom" => Ok(ViewPreset::Bottom),
+            "isometric" => Ok(ViewPreset::Isometric),
+            _ => Err(format!(
+                "expect `front`, `back`, `left`, `right`, `top`, `bottom` or `isometric`, got `{}`",
+                s
+            )),
+        }
+    }
+
+    pub fn main() {
+        let args = Args::parse();
+        requests::set_asset_root(&args.root);
+
+        let image = pollster::block_on(async {
+            let mut state = State::new_headless(args.width, args.height).await?;
+
+            if let Some(scene) = &args.scene {
+                state.load_scene(scene).await?;
+            }
+
+            if let Some(environment) = &args.environment {
+                state.load_environment(environment).await?;
+            }
+
+            if let Some(model) = &args.model {
+                state.load_model("snapshot", model).await?;
+                state.scene.borrow_mut().add_node(
+                    None,
+                    "snapshot",
+                    NodeTransform::default(),
+                    Some(NodeContent::Model("snapshot".to_owned())),
+                );
+
+                if let Some(clip) = state.model_animations("snapshot").first() {
+                    let animation = AnimationPlayer::new(clip.clone(), LoopMode::Repeat);
+                    state.animation_insert("snapshot", animation);
+                }
+            }
+
+            if let Some(time) = args.time {
+                state.seek_animations(time);
+                state.update_animations(0.0);
+            }
+
+            let camera = state.camera.get();
+            let camera = Camera {
+                eye: args.eye.unwrap_or(camera.eye),
+                target: args.target.unwrap_or(camera.target),
+                fovy: args.fovy.unwrap_or(camera.fovy),
+                projection: args.projection.unwrap_or(camera.projection),
+                ..camera
+            };
+            state.set_camera(camera);
+            // The scene frames its models itself unless it places the camera
+            if args.scene.is_none() && args.eye.is_none() && args.target.is_none() {
+                state.frame_all();
+            }
+
+            if let Some(view) = args.view {
+                let camera = state.camera.get();
+                let mut controller = CameraController::default();
+                controller.settings.transition_time = 0.0;
+                controller.look_from(&camera);
+                controller.view(view);
+                controller.update(0.0);
+                state.set_camera(controller.apply(camera));
+            }
+            let mut settings = state.render_settings();
+            settings.exposure = args.exposure.unwrap_or(settings.exposure);
+            settings.tonemapping = args.tonemapping.unwrap_or(settings.tonemapping);
+            settings.bloom = match args.bloom {
+                Some(intensity) if intensity <= 0.0 => None,
+                Some(intensity) => Some(BloomSettings {
+                    intensity,
+                    ..settings.bloom.unwrap_or_default()
+                }),
+                None => settings.bloom,
+            };
+            settings.ssao = match args.ssao {
+                Some(radius) if radius <= 0.0 => None,
+                Some(radius) => Some(SsaoSettings {
+                    radius,
+                    ..settings.ssao.unwrap_or_default()
+                }),
+                None => settings.ssao,
+            };
+            settings.msaa = args.msaa.unwrap_or(settings.msaa);
+            settings.fxaa |= args.fxaa;
+            state.set_render_settings(settings);
+            state.render()?;
+
+            state.read_pixels().await
+        });
+
+        match image.map(|image| image.save(&args.output)) {
+            Ok(Ok(())) => println!("Saved {}", args.output.display()),
+            Ok(Err(e)) => {
+                eprintln!("Failed to save {}: {}", args.output.display(), e);
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to render: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    native::main();
+}
+
+// Snapshots need the local file system, there is nothing to do in a browser
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,2 @@
+pub mod main_player;
+pub mod requests;
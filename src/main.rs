@@ -1,18 +1,28 @@
-use yew::prelude::*;
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use models_player::main_player;
+    use yew::prelude::*;
 
-mod main_player;
-mod requests;
+    #[function_component(App)]
+    fn app() -> Html {
+        html!(
+            <>
+                //Where the graphic show
+                <main_player::MainPlayer />
+            </>
+        )
+    }
 
-#[function_component(App)]
-fn app() -> Html {
-    html!(
-        <>
-            //Where the graphic show
-            <main_player::MainPlayer />
-        </>
-    )
+    pub fn main() {
+        yew::start_app::<App>();
+    }
 }
 
+#[cfg(target_arch = "wasm32")]
 fn main() {
-    yew::start_app::<App>();
+    web::main();
 }
+
+// The viewer runs in a browser, natively there is only `models_player-render`
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {}
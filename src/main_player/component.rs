@@ -0,0 +1,357 @@
+use web_sys::WebGl2RenderingContext;
+use yew::prelude::*;
+use yew_canvas::{Canvas, WithRander};
+
+use super::{
+    player::Player,
+    resources::{
+        background::Background,
+        camera::{CameraMode, DragMode, FlyDirection, Projection, ViewPreset},
+        post::RenderSettings,
+    },
+};
+
+#[derive(Clone, PartialEq)]
+pub(super) struct Rander {
+    pub player: Player,
+    pub scene: String,
+    pub background: Option<Background>,
+    pub render_settings: Option<RenderSettings>,
+    pub camera_mode: Option<CameraMode>,
+}
+
+impl WithRander for Rander {
+    fn rand(self, canvas: &web_sys::HtmlCanvasElement) {
+        let canvas_size = (canvas.width(), canvas.height());
+
+        let (background, render_settings, camera_mode) =
+            (self.background, self.render_settings, self.camera_mode);
+        let on_ready = move |player: &Player| {
+            // Keep the camera of the scene until the user moves it
+            if let Some(state) = player.state() {
+                state.resize(canvas_size.0, canvas_size.1);
+            }
+            if let Some(background) = background {
+                player.set_background(background);
+            }
+            if let Some(settings) = render_settings {
+                player.set_render_settings(settings);
+            }
+            // Only a new mode, `F` may have switched it since the last one
+            if let Some(mode) = camera_mode {
+                if player.set_camera_mode(mode) {
+                    release_pointer(mode);
+                }
+            }
+            player.request_redraw();
+        };
+
+        if self.player.is_ready() {
+            // Another scene file replaces the scene, the props apply on top of it again
+            self.player.set_scene(&self.scene, on_ready.clone());
+            on_ready(&self.player);
+            return;
+        }
+
+        // While the state is built or loads a scene the props wait for it
+        self.player.attach(canvas, &self.scene, on_ready);
+    }
+}
+
+/// Pixels scrolled per line of wheels scrolling by lines
+const WHEEL_LINE_HEIGHT: f32 = 16.0;
+/// Milliseconds and pixels between the taps of a double tap
+const DOUBLE_TAP_TIME: f64 = 300.0;
+const DOUBLE_TAP_DISTANCE: f32 = 30.0;
+
+fn document() -> Option<web_sys::Document> {
+    web_sys::window()?.document()
+}
+
+/// If the mouse looks around in [`CameraMode::Fly`] instead of moving the pointer.
+fn pointer_locked() -> bool {
+    document().and_then(|d| d.pointer_lock_element()).is_some()
+}
+
+fn set_camera_mode(player: &Player, mode: CameraMode) {
+    player.control_camera(|c| c.set_mode(mode));
+    release_pointer(mode);
+}
+
+/// Only flying keeps the mouse locked.
+fn release_pointer(mode: CameraMode) {
+    if mode == CameraMode::Orbit && pointer_locked() {
+        if let Some(document) = document() {
+            document.exit_pointer_lock();
+        }
+    }
+}
+
+/// Keys flying in [`CameraMode::Fly`], by the place on the keyboard whatever its layout.
+fn fly_direction(code: &str) -> Option<FlyDirection> {
+    Some(match code {
+        "KeyW" | "ArrowUp" => FlyDirection::Forward,
+        "KeyS" | "ArrowDown" => FlyDirection::Backward,
+        "KeyA" | "ArrowLeft" => FlyDirection::Left,
+        "KeyD" | "ArrowRight" => FlyDirection::Right,
+        "KeyE" => FlyDirection::Up,
+        "KeyQ" => FlyDirection::Down,
+        _ => return None,
+    })
+}
+
+/// Keys turning the camera to the presets, `1` to `7` in the order of [`ViewPreset::ALL`].
+fn view_preset(code: &str) -> Option<ViewPreset> {
+    let digit = code.strip_prefix("Digit")?.parse::<usize>().ok()?;
+    ViewPreset::ALL.get(digit.checked_sub(1)?).copied()
+}
+
+fn view_label(preset: ViewPreset) -> &'static str {
+    match preset {
+        ViewPreset::Front => "Front",
+        ViewPreset::Back => "Back",
+        ViewPreset::Left => "Left",
+        ViewPreset::Right => "Right",
+        ViewPreset::Top => "Top",
+        ViewPreset::Bottom => "Bottom",
+        ViewPreset::Isometric => "Iso",
+    }
+}
+
+fn toggle_projection(player: &Player) {
+    player.control_camera(|c| {
+        c.set_projection(match c.projection() {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        })
+    });
+}
+
+#[derive(Properties, PartialEq)]
+pub struct MainPlayerProps {
+    /// Scene file inside `static/scene`, changing it loads the other scene
+    #[prop_or_else(|| "main.json".to_owned())]
+    pub scene: String,
+    /// Replaces the background of the scene, [`Background::Transparent`] shows the page
+    #[prop_or_default]
+    pub background: Option<Background>,
+    /// Replaces the exposure, tonemapping and bloom of the scene
+    #[prop_or_default]
+    pub render_settings: Option<RenderSettings>,
+    /// Switches the camera between orbiting and flying, `F` toggles it too
+    #[prop_or_default]
+    pub camera_mode: Option<CameraMode>,
+    /// Shows buttons turning the camera to the [`ViewPreset`]s and switching the projection
+    #[prop_or_default]
+    pub view_buttons: bool,
+}
+
+#[function_component(MainPlayer)]
+pub fn main_player(props: &MainPlayerProps) -> Html {
+    // Every viewer owns its player, it is dropped together with the component
+    let player = use_state(Player::default);
+    // Time and place of the last tap, the next one close to it refocuses
+    let last_tap = use_mut_ref(|| None::<(f64, f32, f32)>);
+
+    // Mouse, pen and touch go the same way, every finger is a pointer of its own
+    let onpointerdown = {
+        let player = (*player).clone();
+        Callback::from(move |e: PointerEvent| {
+            // The middle and right buttons and shift pan, the left one and fingers orbit
+            let mode = if e.button() != 0 || e.shift_key() {
+                DragMode::Pan
+            } else {
+                DragMode::Orbit
+            };
+            let (id, x, y) = (e.pointer_id(), e.offset_x() as f32, e.offset_y() as f32);
+            let element = e.target_dyn_into::<web_sys::Element>();
+
+            // A mouse flying looks around with the pointer locked, until escape
+            let flying = player.control_camera(|c| c.mode()) == Some(CameraMode::Fly);
+            if flying && e.pointer_type() == "mouse" && e.button() == 0 {
+                if let Some(element) = element.filter(|_| !pointer_locked()) {
+                    element.request_pointer_lock();
+                }
+                return;
+            }
+
+            let captured = player.control_camera(|c| {
+                if let Some(element) = &element {
+                    c.set_viewport(
+                        element.client_width() as f32,
+                        element.client_height() as f32,
+                    );
+                }
+                c.pointer_down(id, x, y, mode)
+            });
+            // Keep following the drag outside of the viewer
+            if let (Some(true), Some(element)) = (captured, element) {
+                let _ = element.set_pointer_capture(id);
+            }
+        })
+    };
+
+    let onpointermove = {
+        let player = (*player).clone();
+        Callback::from(move |e: PointerEvent| {
+            if pointer_locked() {
+                let (dx, dy) = (e.movement_x() as f32, e.movement_y() as f32);
+                player.control_camera(|c| c.orbit(dx, dy));
+                return;
+            }
+            let (id, x, y) = (e.pointer_id(), e.offset_x() as f32, e.offset_y() as f32);
+            player.control_camera(|c| c.pointer_move(id, x, y));
+        })
+    };
+
+    let onpointerup = {
+        let player = (*player).clone();
+        Callback::from(move |e: PointerEvent| {
+            let (time, x, y) = (e.time_stamp(), e.offset_x() as f32, e.offset_y() as f32);
+
+            let tapped = player.control_camera(|c| c.pointer_up(e.pointer_id()));
+            if tapped != Some(true) {
+                return;
+            }
+
+            let mut last_tap = last_tap.borrow_mut();
+            match *last_tap {
+                Some((last_time, last_x, last_y))
+                    if time - last_time <= DOUBLE_TAP_TIME
+                        && (x - last_x).hypot(y - last_y) <= DOUBLE_TAP_DISTANCE =>
+                {
+                    *last_tap = None;
+                    player.control_camera(|c| c.refocus(x, y));
+                }
+                _ => *last_tap = Some((time, x, y)),
+            }
+        })
+    };
+
+    // Pointer up cancelling a drag is never a tap
+    let onpointercancel = {
+        let player = (*player).clone();
+        Callback::from(move |e: PointerEvent| {
+            player.control_camera(|c| {
+                c.pointer_up(e.pointer_id());
+            });
+        })
+    };
+
+    let onwheel = {
+        let player = (*player).clone();
+        Callback::from(move |e: WheelEvent| {
+            let delta = match e.delta_mode() {
+                WheelEvent::DOM_DELTA_PIXEL => e.delta_y() as f32,
+                _ => e.delta_y() as f32 * WHEEL_LINE_HEIGHT,
+            };
+            player.control_camera(|c| c.dolly(delta));
+        })
+    };
+
+    let onkeydown = {
+        let player = (*player).clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if let Some(direction) = fly_direction(&e.code()) {
+                e.prevent_default();
+                player.control_camera(|c| c.fly(direction, true));
+            } else if e.code() == "KeyF" && !e.repeat() {
+                let mode = match player.control_camera(|c| c.mode()) {
+                    Some(CameraMode::Orbit) => CameraMode::Fly,
+                    _ => CameraMode::Orbit,
+                };
+                set_camera_mode(&player, mode);
+            } else if let Some(preset) = view_preset(&e.code()) {
+                player.control_camera(|c| c.view(preset));
+            } else if e.code() == "KeyO" && !e.repeat() {
+                toggle_projection(&player);
+            } else if e.code() == "Home" {
+                if let Some(state) = player.state() {
+                    state.frame_all();
+                }
+                player.request_redraw();
+            }
+        })
+    };
+
+    let onkeyup = {
+        let player = (*player).clone();
+        Callback::from(move |e: KeyboardEvent| {
+            if let Some(direction) = fly_direction(&e.code()) {
+                player.control_camera(|c| c.fly(direction, false));
+            }
+        })
+    };
+
+    // Keys released outside of the viewer never come up
+    let onblur = {
+        let player = (*player).clone();
+        Callback::from(move |_: FocusEvent| {
+            player.control_camera(|c| c.stop());
+        })
+    };
+
+    // Right drags pan instead of opening the menu
+    let oncontextmenu = Callback::from(|e: MouseEvent| e.prevent_default());
+
+    // Outside of the viewer, clicking them doesn't drag the camera
+    let view_buttons = props.view_buttons.then(|| {
+        let presets = ViewPreset::ALL
+            .into_iter()
+            .map(|preset| {
+                let player = (*player).clone();
+                let onclick = Callback::from(move |_: MouseEvent| {
+                    player.control_camera(|c| c.view(preset));
+                });
+                html!(<button {onclick}>{view_label(preset)}</button>)
+            })
+            .collect::<Html>();
+        let onclick = {
+            let player = (*player).clone();
+            Callback::from(move |_: MouseEvent| toggle_projection(&player))
+        };
+
+        html!(
+            <div style="position: absolute; top: 8px; left: 8px; display: flex; gap: 4px;">
+                {presets}
+                <button {onclick}>{"Ortho / Persp"}</button>
+            </div>
+        )
+    });
+
+    let rander = Rander {
+        player: (*player).clone(),
+        scene: props.scene.clone(),
+        background: props.background.clone(),
+        render_settings: props.render_settings,
+        camera_mode: props.camera_mode,
+    };
+
+    html!(
+        <div style="position: relative; width: 100%; height: 100%;">
+            <div
+                {onpointerdown}
+                {onpointermove}
+                {onpointerup}
+                {onpointercancel}
+                {onwheel}
+                {onkeydown}
+                {onkeyup}
+                {onblur}
+                {oncontextmenu}
+                tabindex="0"
+                style="width: 100%; height: 100%; touch-action: none; outline: none;"
+            >
+                <Canvas<WebGl2RenderingContext , Rander>
+                    rander={Box::new(rander)}
+                    style="height: 100%; width: 100%;"
+                >
+                    <h1>
+                        {"Sorry, browser u use don't support canvas"}
+                    </h1>
+                </Canvas<WebGl2RenderingContext , Rander>>
+            </div>
+            { for view_buttons }
+        </div>
+    )
+}
@@ -6,12 +6,38 @@ pub type PlayerErrorResult<T> = Result<T, MainPlayerError>;
 pub enum MainPlayerError {
     #[error("Cannt get error from your browser.")]
     RequestDeviceError(#[from] wgpu::RequestDeviceError),
-    #[error("Use wgpu state without init.")]
-    StateNotInitError,
+    #[error("No graphics adapter is available.")]
+    AdapterNotFoundError,
     #[error("Something wrong with the surface of wgpu state.")]
     SurfaceError(#[from] wgpu::SurfaceError),
+    #[error("Only offscreen render targets can be read back.")]
+    TargetNotReadableError,
+    #[error("Failed to map a buffer for reading.")]
+    BufferAsyncError(#[from] wgpu::BufferAsyncError),
     #[error("Failed to load resources from static.")]
     LoadError(#[from] tobj::LoadError),
+    #[error("Failed to parse the gltf file.")]
+    GltfError(#[from] gltf::Error),
+    #[error("The glb file refers to a binary chunk it does not have.")]
+    GltfBlobMissingError,
+    #[error("A buffer view of the gltf file reaches past the end of its buffer.")]
+    GltfBufferRangeError,
+    #[error("The primitive of the gltf mesh {0} is invalid: {1}.")]
+    GltfPrimitiveError(String, String),
+    #[error("The nodes of the gltf file don't form trees: {0}.")]
+    GltfHierarchyError(String),
+    #[error("The skin {0} has {1} joints, more than the shaders support.")]
+    TooManyJointsError(String, usize),
+    #[error("Only base64 data uris are supported.")]
+    UnsupportedUriError,
+    #[error("Failed to decode a data uri.")]
+    DataUriError(#[from] base64::DecodeError),
+    #[error("Failed to parse the scene file.")]
+    SceneParseError(#[from] serde_json::Error),
+    #[error("Invalid scene file: {0}")]
+    SceneError(String),
+    #[error("The faces of a skybox must be squares of the same size.")]
+    SkyboxSizeError,
     #[error("Fail to have a net request.")]
     RequestError(#[from] crate::requests::RequestError)
 }
@@ -0,0 +1,389 @@
+use std::{
+    cell::{Cell, Ref, RefCell},
+    rc::{Rc, Weak},
+};
+
+use web_sys::HtmlCanvasElement;
+
+use super::{
+    error::PlayerErrorResult,
+    resources::{
+        animation::{AnimationClip, AnimationPlayer, LoopMode},
+        background::Background,
+        camera::{CameraController, CameraMode},
+        post::RenderSettings,
+    },
+    scheduler::{FrameScheduler, FrameStats},
+    wgpu_state::State,
+};
+
+/// Handle to one viewer, clones share the same viewer.
+///
+/// Every player owns its own wgpu state, models, camera and animation loop,
+/// all of them are freed once the last handle is dropped.
+#[derive(Clone, Default)]
+pub struct Player {
+    inner: Rc<PlayerInner>,
+}
+
+/// Runs once the state can be used, see [`Player::attach`].
+type OnReady = Box<dyn FnOnce(&Player)>;
+
+#[derive(Default)]
+struct PlayerInner {
+    state: RefCell<Option<State>>,
+    /// While the state is built or loads a scene
+    is_initializing: Cell<bool>,
+    /// Scene file asked for last, `None` once it failed to load so that it can be asked for again
+    scene: RefCell<Option<String>>,
+    /// The scene file waits for the state to be free
+    scene_waiting: Cell<bool>,
+    /// Given last to [`Player::attach`] or [`Player::set_scene`], runs once the scene loaded
+    on_ready: RefCell<Option<OnReady>>,
+    /// Last one given to [`Player::set_background`]
+    background: RefCell<Option<Background>>,
+    /// Last one given to [`Player::set_camera_mode`]
+    camera_mode: Cell<Option<CameraMode>>,
+
+    scheduler: FrameScheduler,
+}
+
+impl PartialEq for Player {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Player {
+    /// The wgpu state, `None` until the first canvas was attached and initialized.
+    pub fn state(&self) -> Option<Ref<'_, State>> {
+        Ref::filter_map(self.inner.state.borrow(), Option::as_ref).ok()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.inner.state.borrow().is_some()
+    }
+
+    /// Start the initialization of the wgpu state on the canvas with the scene file `scene`,
+    /// `on_ready` runs once the state can be used.
+    ///
+    /// Does nothing if the player is already initialized. While initializing or loading a scene
+    /// `scene` replaces the scene file being loaded and `on_ready` the one waiting for it.
+    pub fn attach(
+        &self,
+        canvas: &HtmlCanvasElement,
+        scene: &str,
+        on_ready: impl FnOnce(&Player) + 'static,
+    ) {
+        if self.is_ready() {
+            return;
+        }
+        *self.inner.scene.borrow_mut() = Some(scene.to_owned());
+        *self.inner.on_ready.borrow_mut() = Some(Box::new(on_ready));
+        if self.inner.is_initializing.replace(true) {
+            return;
+        }
+
+        let canvas = canvas.clone();
+        let player = Rc::downgrade(&self.inner);
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let state = Self::init_state(&canvas, &player).await;
+
+            // The viewer may be gone while the state was being built
+            let inner = match player.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            inner.is_initializing.set(false);
+
+            match state {
+                Ok(state) => {
+                    *inner.state.borrow_mut() = Some(state);
+                    Self::run_on_ready(inner);
+                }
+                Err(e) => gloo::console::error!(e.to_string()),
+            }
+        });
+    }
+
+    async fn init_state(
+        canvas: &HtmlCanvasElement,
+        player: &Weak<PlayerInner>,
+    ) -> PlayerErrorResult<State> {
+        let mut state = State::from_canvas(canvas).await?;
+        Self::load_scene(player, &mut state).await?;
+
+        Ok(state)
+    }
+
+    /// Replace the scene with the scene file `scene`, `on_ready` runs once it is loaded,
+    /// or failed to and the current scene stays.
+    ///
+    /// Does nothing before the state is ready or if `scene` is the scene file asked for last,
+    /// use [`Player::attach`] while a scene loads.
+    /// Nothing is drawn while the scene loads.
+    pub fn set_scene(&self, scene: &str, on_ready: impl FnOnce(&Player) + 'static) {
+        if !self.is_ready() || self.inner.scene.borrow().as_deref() == Some(scene) {
+            return;
+        }
+
+        *self.inner.scene.borrow_mut() = Some(scene.to_owned());
+        *self.inner.on_ready.borrow_mut() = Some(Box::new(on_ready));
+        self.inner.scene_waiting.set(true);
+        Self::load_pending_scene(&self.inner);
+    }
+
+    /// Start loading the scene file asked for last if it waits and the state is free.
+    fn load_pending_scene(inner: &Rc<PlayerInner>) {
+        if !inner.scene_waiting.get() {
+            return;
+        }
+
+        // Loading needs the state mutably across awaits, input and frames skip it meanwhile
+        let mut state = match inner.state.try_borrow_mut() {
+            Ok(mut state) => match state.take() {
+                Some(state) => state,
+                None => return,
+            },
+            // Borrowed for now, the next frame tries again
+            Err(_) => {
+                Self::schedule(inner);
+                return;
+            }
+        };
+        inner.scene_waiting.set(false);
+        inner.is_initializing.set(true);
+        let player = Rc::downgrade(inner);
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = Self::load_scene(&player, &mut state).await;
+
+            let inner = match player.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            inner.is_initializing.set(false);
+            *inner.state.borrow_mut() = Some(state);
+            // The scene brought its own background
+            inner.background.take();
+
+            if let Err(e) = result {
+                gloo::console::error!(e.to_string());
+                inner.scene.take();
+                Self::schedule(&inner);
+            }
+            // The latest one, given while loading or before
+            Self::run_on_ready(inner);
+        });
+    }
+
+    fn run_on_ready(inner: Rc<PlayerInner>) {
+        let on_ready = inner.on_ready.take();
+        if let Some(on_ready) = on_ready {
+            on_ready(&Player { inner });
+        }
+    }
+
+    /// Load the scene file asked for last, again if another one was asked for while loading.
+    async fn load_scene(player: &Weak<PlayerInner>, state: &mut State) -> PlayerErrorResult<()> {
+        loop {
+            let scene = match player.upgrade() {
+                Some(inner) => inner.scene.borrow().clone(),
+                None => return Ok(()),
+            };
+            let scene = match scene {
+                Some(scene) => scene,
+                None => return Ok(()),
+            };
+            let result = state.load_scene(&scene).await;
+
+            let is_latest = match player.upgrade() {
+                Some(inner) => inner.scene.borrow().as_deref() == Some(scene.as_str()),
+                None => true,
+            };
+            if is_latest {
+                return result;
+            }
+            // The scene asked for meanwhile may load fine
+            if let Err(e) = result {
+                gloo::console::error!(e.to_string());
+            }
+        }
+    }
+
+    /// Draw a frame on the next display refresh, any number of requests before
+    /// it results in a single frame. Frames keep coming while animations play.
+    pub fn request_redraw(&self) {
+        Self::schedule(&self.inner);
+    }
+
+    /// Draw `background` behind the models once its images are loaded.
+    ///
+    /// Does nothing before the state is ready, or if it is the last background set.
+    pub fn set_background(&self, background: Background) {
+        if !self.is_ready() || self.inner.background.borrow().as_ref() == Some(&background) {
+            return;
+        }
+        *self.inner.background.borrow_mut() = Some(background.clone());
+
+        let player = Rc::downgrade(&self.inner);
+        wasm_bindgen_futures::spawn_local(async move {
+            // Without the state borrowed, scenes can load meanwhile
+            let images = background.request_images().await;
+
+            let inner = match player.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            let result = match inner.state.borrow().as_ref() {
+                Some(state) => {
+                    images.and_then(|images| state.set_loaded_background(background, images))
+                }
+                None => return,
+            };
+            match result {
+                Ok(()) => Self::schedule(&inner),
+                Err(e) => gloo::console::error!(e.to_string()),
+            }
+        });
+    }
+
+    /// Exposure, tonemapping and bloom of the viewer.
+    ///
+    /// Does nothing before the state is ready, or if the settings are the current ones.
+    pub fn set_render_settings(&self, settings: RenderSettings) {
+        let state = match self.state() {
+            Some(state) => state,
+            None => return,
+        };
+        if state.render_settings() == settings {
+            return;
+        }
+
+        state.set_render_settings(settings);
+        self.request_redraw();
+    }
+
+    /// Switch the camera controller to `mode`, returns if it did.
+    ///
+    /// Does nothing before the state is ready or if `mode` is the last one set,
+    /// so that switching by hand in between lasts.
+    pub fn set_camera_mode(&self, mode: CameraMode) -> bool {
+        if !self.is_ready() || self.inner.camera_mode.get() == Some(mode) {
+            return false;
+        }
+        self.inner.camera_mode.set(Some(mode));
+
+        self.control_camera(|c| c.set_mode(mode));
+        true
+    }
+
+    /// Give the camera controller input, the camera follows on the next frame.
+    ///
+    /// Does nothing before the state is ready.
+    pub fn control_camera<T>(&self, f: impl FnOnce(&mut CameraController) -> T) -> Option<T> {
+        let state = self.state()?;
+        let mut controller = state.camera_controller();
+        let result = f(&mut controller);
+        if controller.is_moving() {
+            self.request_redraw();
+        }
+
+        Some(result)
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.inner.scheduler.stats()
+    }
+
+    fn schedule(inner: &Rc<PlayerInner>) {
+        let player = Rc::downgrade(inner);
+        inner
+            .scheduler
+            .request(move |timestamp| Self::frame(&player, timestamp));
+    }
+
+    fn frame(player: &Weak<PlayerInner>, timestamp: f64) {
+        let inner = match player.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let delta = inner.scheduler.begin(timestamp);
+        let start = now();
+
+        let is_animating = match inner.state.borrow().as_ref() {
+            Some(state) => {
+                let is_camera_moving = state.update_camera(delta);
+                state.update_animations(delta);
+                if let Err(e) = state.render() {
+                    gloo::console::error!(e.to_string());
+                }
+
+                state.is_animating() || is_camera_moving
+            }
+            None => false,
+        };
+
+        inner
+            .scheduler
+            .end(delta, (now() - start) as f32, is_animating);
+        if is_animating {
+            Self::schedule(&inner);
+        }
+        Self::load_pending_scene(&inner);
+    }
+
+    /// Start playing `clip` under `label`, an animation with the same label is replaced.
+    ///
+    /// Does nothing before the state is ready.
+    pub fn animation_insert(&self, label: &str, clip: AnimationClip, loop_mode: LoopMode) {
+        let state = match self.state() {
+            Some(state) => state,
+            None => return,
+        };
+
+        let mut animation = AnimationPlayer::new(clip, loop_mode);
+        animation.play();
+        state.animation_insert(label, animation);
+
+        self.request_redraw();
+    }
+
+    pub fn animation_play(&self, label: &str) {
+        self.animation_update(label, AnimationPlayer::play);
+    }
+
+    pub fn animation_pause(&self, label: &str) {
+        self.animation_update(label, AnimationPlayer::pause);
+    }
+
+    /// Jump to `time` seconds of the animation, paused ones show it as well.
+    pub fn animation_seek(&self, label: &str, time: f32) {
+        self.animation_update(label, |a| a.seek(time));
+    }
+
+    fn animation_update(&self, label: &str, f: impl FnOnce(&mut AnimationPlayer)) {
+        if let Some(state) = self.state() {
+            if let Some(mut animation) = state.animation_mut(label) {
+                f(&mut animation);
+            }
+        }
+
+        self.request_redraw();
+    }
+
+    pub fn animation_clear(&self) {
+        if let Some(state) = self.state() {
+            state.animation_clear();
+        }
+    }
+}
+
+/// Milliseconds from the high resolution clock of the page.
+fn now() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map_or(0.0, |p| p.now())
+}
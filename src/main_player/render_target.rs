@@ -0,0 +1,172 @@
+use image::RgbaImage;
+
+use super::error::{MainPlayerError, PlayerErrorResult};
+
+/// Where the frames of a wgpu state end up.
+pub(crate) enum RenderTarget {
+    /// Swap chain of a canvas on the page
+    Surface(wgpu::Surface),
+    /// Texture living only on the gpu, its pixels can be read back
+    Offscreen(wgpu::Texture),
+}
+
+/// A texture to draw one frame into, get from [`RenderTarget::current_frame`].
+pub(crate) struct Frame {
+    pub(crate) view: wgpu::TextureView,
+    output: Option<wgpu::SurfaceTexture>,
+}
+
+impl Frame {
+    /// Show the frame if it belongs to a surface, offscreen frames need nothing.
+    pub(crate) fn present(self) {
+        if let Some(output) = self.output {
+            output.present();
+        }
+    }
+}
+
+impl RenderTarget {
+    pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub(crate) fn offscreen(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+
+        Self::Offscreen(texture)
+    }
+
+    /// Take a new size from the config, the swap chain is reconfigured and
+    /// an offscreen texture recreated.
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        match self {
+            Self::Surface(surface) => surface.configure(device, config),
+            Self::Offscreen(_) => *self = Self::offscreen(device, config),
+        }
+    }
+
+    pub(crate) fn current_frame(&self) -> PlayerErrorResult<Frame> {
+        Ok(match self {
+            Self::Surface(surface) => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+
+                Frame {
+                    view,
+                    output: Some(output),
+                }
+            }
+            Self::Offscreen(texture) => Frame {
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                output: None,
+            },
+        })
+    }
+
+    /// Copy what was rendered last into a buffer, only offscreen targets support this.
+    pub(crate) fn start_readback(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> PlayerErrorResult<Readback> {
+        let texture = match self {
+            Self::Offscreen(texture) => texture,
+            Self::Surface(_) => return Err(MainPlayerError::TargetNotReadableError),
+        };
+
+        let (width, height) = (config.width, config.height);
+
+        // Rows copied out of a texture have to be aligned to 256 bytes
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (4 * width).div_ceil(align) * align;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(Readback {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+        })
+    }
+}
+
+/// Pixels on their way from an offscreen target back to the cpu.
+pub(crate) struct Readback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+}
+
+impl Readback {
+    pub(crate) async fn into_image(self, device: &wgpu::Device) -> PlayerErrorResult<RgbaImage> {
+        let slice = self.buffer.slice(..);
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        // Nothing happens on native until the device is polled
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .await
+            .map_err(|_| MainPlayerError::BufferAsyncError(wgpu::BufferAsyncError))??;
+
+        let pixels = {
+            let padded = slice.get_mapped_range();
+            padded
+                .chunks(self.padded_bytes_per_row as usize)
+                .flat_map(|row| &row[..4 * self.width as usize])
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        self.buffer.unmap();
+
+        Ok(RgbaImage::from_raw(self.width, self.height, pixels)
+            .expect("readback buffer should hold exactly width * height pixels"))
+    }
+}
@@ -0,0 +1,566 @@
+use cgmath::{EuclideanSpace, InnerSpace, VectorSpace};
+use serde::Deserialize;
+
+use super::model::skeleton::NodeTransform;
+use crate::main_player::wgpu_state::State;
+
+/// How values between two keyframes are calculated, same as the samplers of glTF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Keep the value of the previous keyframe
+    Step,
+    #[default]
+    Linear,
+    /// Hermite spline, every keyframe carries an in and an out tangent
+    CubicSpline,
+}
+
+/// Something keyframes can be interpolated over.
+pub trait Animatable: Copy {
+    fn lerp(self, other: Self, amount: f32) -> Self;
+
+    fn scale(self, factor: f32) -> Self;
+
+    /// Cubic hermite spline between `self` and `other`,
+    /// the tangents are already scaled by the duration between both keyframes.
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, amount: f32) -> Self;
+}
+
+impl Animatable for cgmath::Vector3<f32> {
+    fn lerp(self, other: Self, amount: f32) -> Self {
+        VectorSpace::lerp(self, other, amount)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, amount: f32) -> Self {
+        let (h00, h10, h01, h11) = hermite_basis(amount);
+        self * h00 + out_tangent * h10 + other * h01 + in_tangent * h11
+    }
+}
+
+impl Animatable for cgmath::Quaternion<f32> {
+    fn lerp(self, other: Self, amount: f32) -> Self {
+        self.slerp(other, amount)
+    }
+
+    fn scale(self, factor: f32) -> Self {
+        self * factor
+    }
+
+    fn hermite(self, out_tangent: Self, other: Self, in_tangent: Self, amount: f32) -> Self {
+        let (h00, h10, h01, h11) = hermite_basis(amount);
+        (self * h00 + out_tangent * h10 + other * h01 + in_tangent * h11).normalize()
+    }
+}
+
+/// Curve applied to the progress between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Map the progress `t` in `0..=1` onto the curve.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
+
+fn hermite_basis(t: f32) -> (f32, f32, f32, f32) {
+    let (t2, t3) = (t * t, t * t * t);
+
+    (
+        2.0 * t3 - 3.0 * t2 + 1.0,
+        t3 - 2.0 * t2 + t,
+        -2.0 * t3 + 3.0 * t2,
+        t3 - t2,
+    )
+}
+
+/// Values of one property over time.
+#[derive(Debug, Clone)]
+pub struct Keyframes<T> {
+    /// Seconds, ascending
+    pub times: Vec<f32>,
+    /// One value per keyframe, or `[in tangent, value, out tangent]` per keyframe
+    /// for [`Interpolation::CubicSpline`]
+    pub values: Vec<T>,
+    pub interpolation: Interpolation,
+    pub easing: Easing,
+}
+
+impl<T: Animatable> Keyframes<T> {
+    /// Value at `time`, clamped to the first and last keyframe.
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let value = |i: usize| match self.interpolation {
+            Interpolation::CubicSpline => self.values.get(i * 3 + 1).copied(),
+            _ => self.values.get(i).copied(),
+        };
+
+        let last = self.times.len().checked_sub(1)?;
+        if time <= self.times[0] {
+            return value(0);
+        }
+        if time >= self.times[last] {
+            return value(last);
+        }
+
+        // The keyframe after `time`, never the first one because of the clamping above
+        let next = self.times.partition_point(|&t| t <= time);
+        let prev = next - 1;
+
+        let delta = self.times[next] - self.times[prev];
+        let amount = self.easing.apply((time - self.times[prev]) / delta);
+
+        match self.interpolation {
+            Interpolation::Step => value(prev),
+            Interpolation::Linear => Some(value(prev)?.lerp(value(next)?, amount)),
+            Interpolation::CubicSpline => {
+                let out_tangent = *self.values.get(prev * 3 + 2)?;
+                let in_tangent = *self.values.get(next * 3)?;
+
+                Some(value(prev)?.hermite(
+                    out_tangent.scale(delta),
+                    value(next)?,
+                    in_tangent.scale(delta),
+                    amount,
+                ))
+            }
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+}
+
+/// Property of a node a track drives.
+#[derive(Debug, Clone)]
+pub enum NodeTrack {
+    Translation(Keyframes<cgmath::Vector3<f32>>),
+    Rotation(Keyframes<cgmath::Quaternion<f32>>),
+    Scale(Keyframes<cgmath::Vector3<f32>>),
+}
+
+impl NodeTrack {
+    pub fn duration(&self) -> f32 {
+        match self {
+            NodeTrack::Translation(k) | NodeTrack::Scale(k) => k.duration(),
+            NodeTrack::Rotation(k) => k.duration(),
+        }
+    }
+
+    fn apply(&self, time: f32, transform: &mut NodeTransform) {
+        match self {
+            NodeTrack::Translation(k) => {
+                if let Some(v) = k.sample(time) {
+                    transform.translation = v;
+                }
+            }
+            NodeTrack::Rotation(k) => {
+                if let Some(v) = k.sample(time) {
+                    transform.rotation = v;
+                }
+            }
+            NodeTrack::Scale(k) => {
+                if let Some(v) = k.sample(time) {
+                    transform.scale = v;
+                }
+            }
+        }
+    }
+}
+
+/// Keyframes together with the property of the state they drive.
+#[derive(Debug, Clone)]
+pub enum Track {
+    /// Node of the skeleton of the model loaded under the key `model`
+    Node {
+        model: String,
+        node: usize,
+        track: NodeTrack,
+    },
+    /// Node of the scene called `node`
+    SceneNode {
+        node: String,
+        track: NodeTrack,
+    },
+    /// Light of the state at index `light`
+    LightPosition {
+        light: usize,
+        keyframes: Keyframes<cgmath::Vector3<f32>>,
+    },
+    LightColor {
+        light: usize,
+        keyframes: Keyframes<cgmath::Vector3<f32>>,
+    },
+    CameraEye(Keyframes<cgmath::Vector3<f32>>),
+    CameraTarget(Keyframes<cgmath::Vector3<f32>>),
+}
+
+impl Track {
+    pub fn duration(&self) -> f32 {
+        match self {
+            Track::Node { track, .. } | Track::SceneNode { track, .. } => track.duration(),
+            Track::LightPosition { keyframes: k, .. }
+            | Track::LightColor { keyframes: k, .. }
+            | Track::CameraEye(k)
+            | Track::CameraTarget(k) => k.duration(),
+        }
+    }
+
+    /// Write the value at `time` into the state, nothing happens if the target is missing.
+    fn apply(&self, time: f32, state: &State) {
+        match self {
+            Track::Node { model, node, track } => {
+                let model = match state.obj_models.get(model) {
+                    Some(model) => model,
+                    None => return,
+                };
+                if let Some(node) = model.pose.borrow_mut().get_mut(*node) {
+                    track.apply(time, node);
+                }
+            }
+            Track::SceneNode { node, track } => {
+                let mut scene = state.scene.borrow_mut();
+                if let Some(id) = scene.find(node) {
+                    if let Some(node) = scene.node_mut(id) {
+                        track.apply(time, &mut node.transform);
+                    }
+                }
+            }
+            Track::LightPosition { light, keyframes } => {
+                if let Some(v) = keyframes.sample(time) {
+                    state.update_light(*light, |l| l.position = cgmath::Point3::from_vec(v));
+                }
+            }
+            Track::LightColor { light, keyframes } => {
+                if let Some(v) = keyframes.sample(time) {
+                    state.update_light(*light, |l| l.color = v.into());
+                }
+            }
+            Track::CameraEye(k) => {
+                if let Some(v) = k.sample(time) {
+                    let mut camera = state.camera.get();
+                    camera.eye = cgmath::Point3::from_vec(v);
+                    state.follow_camera(camera);
+                }
+            }
+            Track::CameraTarget(k) => {
+                if let Some(v) = k.sample(time) {
+                    let mut camera = state.camera.get();
+                    camera.target = cgmath::Point3::from_vec(v);
+                    state.follow_camera(camera);
+                }
+            }
+        }
+    }
+}
+
+/// Named set of tracks played together, like one animation of a glTF file.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    pub tracks: Vec<Track>,
+    /// Seconds until the last keyframe of all tracks
+    pub duration: f32,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, tracks: Vec<Track>) -> Self {
+        let duration = tracks.iter().map(Track::duration).fold(0.0, f32::max);
+
+        Self {
+            name: name.to_string(),
+            tracks,
+            duration,
+        }
+    }
+
+    /// Point the node tracks at the model loaded under `model`.
+    pub fn retarget(&mut self, model: &str) {
+        for track in &mut self.tracks {
+            if let Track::Node { model: m, .. } = track {
+                *m = model.to_string();
+            }
+        }
+    }
+
+    /// Write the values of every track at `time` into the state.
+    pub fn apply(&self, time: f32, state: &State) {
+        self.tracks.iter().for_each(|t| t.apply(time, state));
+    }
+}
+
+/// What happens when the playback reaches the end of the clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    /// Stop at the end
+    #[default]
+    Once,
+    /// Start over from the beginning
+    Repeat,
+    /// Play backwards once the end is reached, then forwards again
+    PingPong,
+}
+
+/// Playback of one clip driven by elapsed time.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    clip: AnimationClip,
+    /// Seconds since the start, not wrapped
+    elapsed: f32,
+    pub speed: f32,
+    pub loop_mode: LoopMode,
+    is_playing: bool,
+}
+
+impl AnimationPlayer {
+    /// A paused playback at the start of `clip`.
+    pub fn new(clip: AnimationClip, loop_mode: LoopMode) -> Self {
+        Self {
+            clip,
+            elapsed: 0.0,
+            speed: 1.0,
+            loop_mode,
+            is_playing: false,
+        }
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+        &self.clip
+    }
+
+    pub fn play(&mut self) {
+        // Replaying a finished clip starts over
+        if self.loop_mode == LoopMode::Once && self.elapsed >= self.clip.duration {
+            self.elapsed = 0.0;
+        }
+        self.is_playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.is_playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing
+    }
+
+    /// Jump to `time` seconds of the clip.
+    pub fn seek(&mut self, time: f32) {
+        self.elapsed = time.max(0.0);
+    }
+
+    /// Position in the clip in seconds, with looping applied.
+    pub fn time(&self) -> f32 {
+        let duration = self.clip.duration;
+        if duration <= 0.0 {
+            return 0.0;
+        }
+
+        match self.loop_mode {
+            LoopMode::Once => self.elapsed.min(duration),
+            LoopMode::Repeat => self.elapsed % duration,
+            LoopMode::PingPong => {
+                let t = self.elapsed % (duration * 2.0);
+                if t > duration {
+                    duration * 2.0 - t
+                } else {
+                    t
+                }
+            }
+        }
+    }
+
+    /// Move the playback `delta` seconds forward, clips played once stop at their end.
+    pub fn advance(&mut self, delta: f32) {
+        if !self.is_playing {
+            return;
+        }
+
+        self.elapsed = (self.elapsed + delta * self.speed).max(0.0);
+        if self.loop_mode == LoopMode::Once && self.elapsed >= self.clip.duration {
+            self.elapsed = self.clip.duration;
+            self.is_playing = false;
+        }
+    }
+
+    pub fn apply(&self, state: &State) {
+        self.clip.apply(self.time(), state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn keyframes(
+        values: Vec<Vector3<f32>>,
+        interpolation: Interpolation,
+    ) -> Keyframes<Vector3<f32>> {
+        Keyframes {
+            times: vec![0.0, 1.0, 3.0],
+            values,
+            interpolation,
+            easing: Easing::Linear,
+        }
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn sample_clamps_to_first_and_last_keyframe() {
+        let k = keyframes(
+            vec![Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+            Interpolation::Linear,
+        );
+
+        assert_close(k.sample(-1.0).unwrap(), Vector3::unit_x());
+        assert_close(k.sample(5.0).unwrap(), Vector3::unit_z());
+        assert_eq!(k.duration(), 3.0);
+    }
+
+    #[test]
+    fn sample_linear_and_step() {
+        let values = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(2.0, 4.0, 0.0),
+        ];
+
+        let linear = keyframes(values.clone(), Interpolation::Linear);
+        assert_close(linear.sample(0.5).unwrap(), Vector3::new(1.0, 0.0, 0.0));
+        assert_close(linear.sample(2.0).unwrap(), Vector3::new(2.0, 2.0, 0.0));
+
+        let step = keyframes(values, Interpolation::Step);
+        assert_close(step.sample(0.99).unwrap(), Vector3::new(0.0, 0.0, 0.0));
+        assert_close(step.sample(1.0).unwrap(), Vector3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sample_cubic_spline() {
+        // Zero tangents ease in and out, the middle is still the average
+        let (zero, a, b) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+        );
+        let k = Keyframes {
+            times: vec![0.0, 2.0],
+            values: vec![zero, a, zero, zero, b, zero],
+            interpolation: Interpolation::CubicSpline,
+            easing: Easing::Linear,
+        };
+
+        assert_close(k.sample(0.0).unwrap(), a);
+        assert_close(k.sample(1.0).unwrap(), Vector3::new(2.0, 0.0, 0.0));
+        assert!(k.sample(0.5).unwrap().x < 1.0);
+        assert_close(k.sample(2.0).unwrap(), b);
+    }
+
+    #[test]
+    fn sample_without_keyframes_or_values() {
+        let empty = keyframes(vec![], Interpolation::Linear);
+        assert!(empty.sample(0.0).is_none());
+
+        let missing = keyframes(vec![Vector3::unit_x()], Interpolation::Linear);
+        assert!(missing.sample(0.5).is_none());
+    }
+
+    #[test]
+    fn easing_keeps_the_ends() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert!(easing.apply(0.0).abs() < 1e-6);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6);
+        }
+
+        assert!(Easing::EaseIn.apply(0.5) < 0.5);
+        assert!(Easing::EaseOut.apply(0.5) > 0.5);
+        assert!((Easing::EaseInOut.apply(0.5) - 0.5).abs() < 1e-6);
+        assert!((Easing::EaseInOut.apply(0.25) + Easing::EaseInOut.apply(0.75) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn easing_changes_the_sampled_progress() {
+        let mut k = keyframes(
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+            ],
+            Interpolation::Linear,
+        );
+        k.easing = Easing::EaseIn;
+
+        assert_close(k.sample(0.5).unwrap(), Vector3::new(0.125, 0.0, 0.0));
+    }
+
+    fn player(loop_mode: LoopMode) -> AnimationPlayer {
+        let track = Track::CameraEye(keyframes(
+            vec![Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+            Interpolation::Linear,
+        ));
+        AnimationPlayer::new(AnimationClip::new("clip", vec![track]), loop_mode)
+    }
+
+    #[test]
+    fn player_loop_modes() {
+        let mut once = player(LoopMode::Once);
+        once.play();
+        once.advance(4.0);
+        assert_eq!(once.time(), 3.0);
+        assert!(!once.is_playing());
+        once.play();
+        assert_eq!(once.time(), 0.0);
+
+        let mut repeat = player(LoopMode::Repeat);
+        repeat.play();
+        repeat.advance(4.0);
+        assert!((repeat.time() - 1.0).abs() < 1e-6);
+
+        let mut ping_pong = player(LoopMode::PingPong);
+        ping_pong.play();
+        ping_pong.advance(4.0);
+        assert!((ping_pong.time() - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn paused_player_does_not_advance() {
+        let mut p = player(LoopMode::Repeat);
+        p.advance(1.0);
+        assert_eq!(p.time(), 0.0);
+
+        p.seek(2.0);
+        assert_eq!(p.time(), 2.0);
+    }
+}
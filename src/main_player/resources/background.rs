@@ -0,0 +1,407 @@
+use bytemuck::Zeroable;
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+
+use crate::{
+    main_player::error::{MainPlayerError, PlayerErrorResult},
+    requests,
+};
+
+use super::{camera::Camera, environment::Environment, shader, texture};
+
+/// What the models are drawn in front of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Background {
+    /// Linear rgba
+    Color(wgpu::Color),
+    /// Linear rgba, blended from the bottom to the top of the view
+    Gradient {
+        top: wgpu::Color,
+        bottom: wgpu::Color,
+    },
+    /// Image inside `static/image`, stretched over the view
+    Image(String),
+    /// Images inside `static/image` on the faces +x, -x, +y, -y, +z, -z of a cube around the camera
+    Skybox([String; 6]),
+    /// The environment of [`State::load_environment`](crate::main_player::State::load_environment),
+    /// around the camera
+    Environment,
+    /// Nothing, the page shows through the canvas
+    Transparent,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Self::Color(wgpu::Color::BLACK)
+    }
+}
+
+impl Background {
+    /// Images the background needs, requested before [`BackgroundPass::set`].
+    pub(crate) async fn request_images(&self) -> PlayerErrorResult<Vec<requests::Image>> {
+        let names = match self {
+            Self::Image(name) => std::slice::from_ref(name),
+            Self::Skybox(faces) => &faces[..],
+            _ => &[],
+        };
+
+        let mut images = Vec::new();
+        for name in names {
+            images.push(requests::Image::from_name(name).await?);
+        }
+
+        Ok(images)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+    // Camera axes scaled to the edges of the view, `w` is only padding
+    forward: [f32; 4],
+    right: [f32; 4],
+    up: [f32; 4],
+}
+
+/// Draws the [`Background`] first thing in the render pass.
+pub(crate) struct BackgroundPass {
+    background: Background,
+    /// Image of [`Background::Image`], 1 pixel for the other kinds
+    image: texture::Texture,
+    /// Cube of [`Background::Skybox`]
+    skybox: Option<(wgpu::Texture, wgpu::TextureView)>,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    shader: shader::Shader,
+    pipeline_layout: wgpu::PipelineLayout,
+    color_format: wgpu::TextureFormat,
+    /// Gradient, image and cube, rebuilt when the sample count changes
+    pipelines: [wgpu::RenderPipeline; 3],
+}
+
+impl BackgroundPass {
+    pub(crate) async fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_format: wgpu::TextureFormat,
+        environment: &Environment,
+    ) -> PlayerErrorResult<Self> {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("background_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("Background Shader", "background.wgsl").await?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = Self::create_pipelines(device, &shader, &pipeline_layout, color_format, 1);
+
+        let image = texture::Texture::from_image(
+            device,
+            queue,
+            requests::Image::from_color([0, 0, 0, 255]),
+            Some("Background Image"),
+            false,
+        )?;
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Background Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background Buffer"),
+            contents: bytemuck::cast_slice(&[BackgroundUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::create_bind_group(
+            device,
+            &layout,
+            &buffer,
+            &image.view,
+            &environment.cube,
+            &sampler,
+        );
+
+        Ok(Self {
+            background: Background::default(),
+            image,
+            skybox: None,
+            sampler,
+            buffer,
+            bind_group,
+            layout,
+            shader,
+            pipeline_layout,
+            color_format,
+            pipelines,
+        })
+    }
+
+    fn create_pipelines(
+        device: &wgpu::Device,
+        shader: &shader::Shader,
+        layout: &wgpu::PipelineLayout,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> [wgpu::RenderPipeline; 3] {
+        ["fs_gradient", "fs_image", "fs_cube"].map(|entry_point| {
+            shader.create_fullscreen_pipeline(
+                device,
+                layout,
+                entry_point,
+                color_format,
+                Some(texture::Texture::DEPTH_FORMAT),
+                shader::PipelineOptions {
+                    depth_write: false,
+                    sample_count,
+                    ..Default::default()
+                },
+            )
+        })
+    }
+
+    /// Follow the sample count of the frame the background is drawn into.
+    pub(crate) fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipelines = Self::create_pipelines(
+            device,
+            &self.shader,
+            &self.pipeline_layout,
+            self.color_format,
+            sample_count,
+        );
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        image: &wgpu::TextureView,
+        cube: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(image),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(cube),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("background_bind_group"),
+        })
+    }
+
+    pub(crate) fn background(&self) -> &Background {
+        &self.background
+    }
+
+    /// Switch to `background`, `images` are the ones of [`Background::request_images`].
+    pub(crate) fn set(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        background: Background,
+        images: Vec<requests::Image>,
+        environment: &Environment,
+    ) -> PlayerErrorResult<()> {
+        match &background {
+            Background::Image(name) => {
+                let image = images.into_iter().next().ok_or_else(|| {
+                    MainPlayerError::SceneError(format!("background image {} is missing", name))
+                })?;
+                self.image = texture::Texture::from_image(device, queue, image, Some(name), false)?;
+            }
+            Background::Skybox(_) => self.skybox = Some(create_skybox(device, queue, images)?),
+            _ => (),
+        }
+        self.background = background;
+        self.rebind(device, environment);
+
+        Ok(())
+    }
+
+    /// Bind the current textures, after the environment changed.
+    pub(crate) fn rebind(&mut self, device: &wgpu::Device, environment: &Environment) {
+        let cube = match (&self.background, &self.skybox) {
+            (Background::Skybox(_), Some((_, view))) => view,
+            _ => &environment.cube,
+        };
+
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.layout,
+            &self.buffer,
+            &self.image.view,
+            cube,
+            &self.sampler,
+        );
+    }
+
+    /// Color the render pass is cleared with.
+    pub(crate) fn clear_color(&self) -> wgpu::Color {
+        match self.background {
+            Background::Color(color) => color,
+            Background::Transparent => wgpu::Color::TRANSPARENT,
+            _ => wgpu::Color::BLACK,
+        }
+    }
+
+    /// Follow the camera, cubes are seen through it.
+    pub(crate) fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        let (top, bottom) = match self.background {
+            Background::Gradient { top, bottom } => (top, bottom),
+            _ => (wgpu::Color::BLACK, wgpu::Color::BLACK),
+        };
+        let color = |c: wgpu::Color| [c.r as f32, c.g as f32, c.b as f32, c.a as f32];
+
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        let half_height = (camera.fovy.to_radians() / 2.0).tan();
+        let half_width = half_height * camera.aspect;
+
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[BackgroundUniform {
+                top: color(top),
+                bottom: color(bottom),
+                forward: forward.extend(0.0).into(),
+                right: (right * half_width).extend(0.0).into(),
+                up: (up * half_height).extend(0.0).into(),
+            }]),
+        );
+    }
+
+    pub(crate) fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let pipeline = match self.background {
+            Background::Gradient { .. } => &self.pipelines[0],
+            Background::Image(_) => &self.pipelines[1],
+            Background::Skybox(_) | Background::Environment => &self.pipelines[2],
+            Background::Color(_) | Background::Transparent => return,
+        };
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Cube texture of six square srgb faces.
+fn create_skybox(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    faces: Vec<requests::Image>,
+) -> PlayerErrorResult<(wgpu::Texture, wgpu::TextureView)> {
+    let (size, _) = faces.first().map_or((0, 0), requests::Image::dimensions);
+    if faces.len() != 6 || faces.iter().any(|f| f.dimensions() != (size, size)) {
+        return Err(MainPlayerError::SkyboxSizeError);
+    }
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Skybox"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    for (layer, face) in faces.into_iter().enumerate() {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer as u32,
+                },
+            },
+            &face.into_diffuse_rgba(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(4 * size),
+                rows_per_image: std::num::NonZeroU32::new(size),
+            },
+            wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    });
+
+    Ok((texture, view))
+}
@@ -0,0 +1,254 @@
+use cgmath::{EuclideanSpace, InnerSpace, Transform};
+
+/// Axis aligned bounding box, empty while `min` is above `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Point3<f32>,
+    pub max: cgmath::Point3<f32>,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: cgmath::Point3::new(f32::MAX, f32::MAX, f32::MAX),
+        max: cgmath::Point3::new(f32::MIN, f32::MIN, f32::MIN),
+    };
+
+    pub fn from_points(points: impl IntoIterator<Item = cgmath::Point3<f32>>) -> Self {
+        points.into_iter().fold(Self::EMPTY, |aabb, point| {
+            aabb.union(Self {
+                min: point,
+                max: point,
+            })
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: cgmath::Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: cgmath::Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// The origin for an empty box.
+    pub fn center(&self) -> cgmath::Point3<f32> {
+        if self.is_empty() {
+            return cgmath::Point3::origin();
+        }
+        self.min.midpoint(self.max)
+    }
+
+    pub fn corners(&self) -> [cgmath::Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            cgmath::Point3::new(min.x, min.y, min.z),
+            cgmath::Point3::new(max.x, min.y, min.z),
+            cgmath::Point3::new(min.x, max.y, min.z),
+            cgmath::Point3::new(max.x, max.y, min.z),
+            cgmath::Point3::new(min.x, min.y, max.z),
+            cgmath::Point3::new(max.x, min.y, max.z),
+            cgmath::Point3::new(min.x, max.y, max.z),
+            cgmath::Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Box around the corners moved by `matrix`, it grows with rotations.
+    pub fn transformed(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(self.corners().map(|c| matrix.transform_point(c)))
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+/// Sphere holding a set of points, usually tighter than the box around them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: cgmath::Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Sphere around the middle of the box of `points` reaching the farthest of them,
+    /// `None` without points.
+    pub fn from_points(points: &[cgmath::Point3<f32>]) -> Option<Self> {
+        let aabb = Aabb::from_points(points.iter().copied());
+        if aabb.is_empty() {
+            return None;
+        }
+
+        let center = aabb.center();
+        let radius = points
+            .iter()
+            .map(|&p| (p - center).magnitude2())
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(Self { center, radius })
+    }
+
+    /// Smallest sphere holding both.
+    pub fn union(self, other: Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        Self {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    /// Sphere moved by `matrix`, the radius grows with its largest scale.
+    pub fn transformed(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        let scale = [matrix.x, matrix.y, matrix.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0, f32::max);
+        Self {
+            center: matrix.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32, z: f32) -> cgmath::Point3<f32> {
+        cgmath::Point3::new(x, y, z)
+    }
+
+    fn sphere(center: cgmath::Point3<f32>, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    fn assert_sphere_close(a: BoundingSphere, b: BoundingSphere) {
+        assert!(
+            (a.center - b.center).magnitude() < 1e-5 && (a.radius - b.radius).abs() < 1e-5,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn aabb_from_points() {
+        let aabb = Aabb::from_points([point(1.0, -2.0, 3.0), point(-1.0, 4.0, 0.0)]);
+        assert_eq!(aabb.min, point(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, point(1.0, 4.0, 3.0));
+        assert_eq!(aabb.center(), point(0.0, 1.0, 1.5));
+        assert!(!aabb.is_empty());
+    }
+
+    #[test]
+    fn empty_aabb() {
+        let empty = Aabb::from_points([]);
+        assert!(empty.is_empty());
+        assert_eq!(empty, Aabb::default());
+        assert_eq!(empty.center(), cgmath::Point3::origin());
+
+        let aabb = Aabb::from_points([point(1.0, 1.0, 1.0)]);
+        assert!(!aabb.is_empty());
+        assert_eq!(empty.union(aabb), aabb);
+        assert_eq!(aabb.union(empty), aabb);
+        assert!(empty
+            .transformed(&cgmath::Matrix4::from_scale(2.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn aabb_union() {
+        let a = Aabb::from_points([point(0.0, 0.0, 0.0), point(1.0, 1.0, 1.0)]);
+        let b = Aabb::from_points([point(2.0, -1.0, 0.5), point(3.0, 0.5, 0.5)]);
+        let union = a.union(b);
+        assert_eq!(union.min, point(0.0, -1.0, 0.0));
+        assert_eq!(union.max, point(3.0, 1.0, 1.0));
+        assert_eq!(union, b.union(a));
+    }
+
+    #[test]
+    fn aabb_grows_with_rotations() {
+        let aabb = Aabb::from_points([point(-1.0, -1.0, -1.0), point(1.0, 1.0, 1.0)]);
+        let moved = aabb.transformed(&cgmath::Matrix4::from_translation(cgmath::vec3(
+            1.0, 2.0, 3.0,
+        )));
+        assert_eq!(moved.center(), point(1.0, 2.0, 3.0));
+
+        let rotated = aabb.transformed(&cgmath::Matrix4::from_angle_y(cgmath::Deg(45.0)));
+        assert!((rotated.max.x - 2f32.sqrt()).abs() < 1e-5);
+        assert!((rotated.max.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_from_points() {
+        assert!(BoundingSphere::from_points(&[]).is_none());
+
+        let points = [
+            point(-1.0, 0.0, 0.0),
+            point(3.0, 0.0, 0.0),
+            point(1.0, 1.0, 0.0),
+        ];
+        let bounds = BoundingSphere::from_points(&points).unwrap();
+        assert_sphere_close(bounds, sphere(point(1.0, 0.5, 0.0), 4.25f32.sqrt()));
+        assert!(points
+            .iter()
+            .all(|&p| (p - bounds.center).magnitude() <= bounds.radius + 1e-5));
+    }
+
+    #[test]
+    fn sphere_union_holds_both() {
+        let a = sphere(point(0.0, 0.0, 0.0), 1.0);
+        let b = sphere(point(4.0, 0.0, 0.0), 1.0);
+        let union = a.union(b);
+        assert_sphere_close(union, sphere(point(2.0, 0.0, 0.0), 3.0));
+        assert_sphere_close(b.union(a), union);
+
+        let c = sphere(point(0.0, 3.0, 0.0), 2.0);
+        let union = a.union(c);
+        for s in [a, c] {
+            let reach = (s.center - union.center).magnitude() + s.radius;
+            assert!(reach <= union.radius + 1e-5);
+        }
+    }
+
+    #[test]
+    fn sphere_union_inside_the_other() {
+        let big = sphere(point(0.0, 0.0, 0.0), 5.0);
+        let small = sphere(point(1.0, 1.0, 0.0), 1.0);
+        assert_eq!(big.union(small), big);
+        assert_eq!(small.union(big), big);
+        assert_eq!(big.union(big), big);
+    }
+
+    #[test]
+    fn sphere_grows_with_the_largest_scale() {
+        let s = sphere(point(1.0, 0.0, 0.0), 1.0);
+        let matrix = cgmath::Matrix4::from_translation(cgmath::vec3(0.0, 1.0, 0.0))
+            * cgmath::Matrix4::from_nonuniform_scale(2.0, 3.0, 1.0);
+        assert_sphere_close(s.transformed(&matrix), sphere(point(2.0, 1.0, 0.0), 3.0));
+    }
+}
@@ -1,4 +1,24 @@
-use cgmath::SquareMatrix;
+use cgmath::{InnerSpace, SquareMatrix};
+use serde::Deserialize;
+
+use super::bounds::BoundingSphere;
+
+mod controller;
+
+pub use controller::{
+    CameraController, CameraMode, ControllerSettings, DragMode, FlyDirection, ViewPreset,
+};
+
+/// How the view is projected on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Parallel lines stay parallel, the view shows as much around the target
+    /// as the perspective one of `fovy` does
+    Orthographic,
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
@@ -9,6 +29,7 @@ pub struct Camera {
     pub fovy: f32,
     pub znear: f32,
     pub zfar: f32,
+    pub projection: Projection,
 }
 
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
@@ -21,9 +42,63 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 impl Camera {
     pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
 
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        self.build_projection_matrix() * view
+    }
+
+    /// From view space into the clip space of wgpu, depth 0 at `znear` and 1 at `zfar`.
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let proj = match self.projection {
+            Projection::Perspective => {
+                cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar)
+            }
+            Projection::Orthographic => {
+                let half_height =
+                    (self.eye - self.target).magnitude() * (self.fovy.to_radians() / 2.0).tan();
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        };
+
+        OPENGL_TO_WGPU_MATRIX * proj
+    }
+
+    /// Look at all of `sphere` from the direction the camera looks in now.
+    /// The dolly limits of `settings` follow its size, from inside of it to where it's a speck,
+    /// and the near and far planes cut nothing off between them.
+    pub fn frame(&self, sphere: BoundingSphere, settings: &mut ControllerSettings) -> Camera {
+        // A single point still gets some room around it
+        let radius = sphere.radius.max(1e-3);
+        settings.min_distance = radius * 0.01;
+        settings.max_distance = radius * 50.0;
+
+        // The sphere touches the narrower side of the view
+        let half_fovy = self.fovy.to_radians() / 2.0;
+        let half_fovx = (half_fovy.tan() * self.aspect).atan();
+        let distance = (radius / half_fovy.min(half_fovx).sin())
+            .clamp(settings.min_distance, settings.max_distance);
+
+        let direction = (self.eye - self.target).normalize();
+        let direction = if direction.x.is_finite() {
+            direction
+        } else {
+            cgmath::Vector3::unit_z()
+        };
+
+        Camera {
+            eye: sphere.center + direction * distance,
+            target: sphere.center,
+            znear: settings.min_distance * 0.1,
+            zfar: settings.max_distance + radius,
+            ..*self
+        }
     }
 
     pub fn get_pos(&self) -> (f32, f32, f32) {
@@ -46,9 +121,81 @@ impl CameraUniform {
         }
     }
 
+    /// Uniform of a view projection built elsewhere, like the one of a shadow casting light.
+    pub(crate) fn from_view_proj(view_proj: cgmath::Matrix4<f32>) -> Self {
+        Self {
+            view_position: [0.0; 4],
+            view_proj: view_proj.into(),
+        }
+    }
+
+    /// Projection the uniform of `camera` draws with, `view_proj` without the view.
+    /// The SSAO reconstructs view space from the depth buffer with its inverse.
+    pub(crate) fn projection(camera: &Camera) -> cgmath::Matrix4<f32> {
+        camera.build_projection_matrix()
+    }
+
     pub fn update_view_proj(&mut self, camera: &Camera) {
         // We're using Vector4 because of the uniforms 16 byte spacing requirement
         self.view_position = camera.eye.to_homogeneous().into();
-        self.view_proj = (OPENGL_TO_WGPU_MATRIX * camera.build_view_projection_matrix()).into();
+        self.view_proj = camera.build_view_projection_matrix().into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Transform;
+
+    fn camera(projection: Projection) -> Camera {
+        Camera {
+            eye: cgmath::Point3::new(0.0, 0.0, 10.0),
+            target: cgmath::Point3::new(0.0, 0.0, 0.0),
+            up: cgmath::Vector3::unit_y(),
+            aspect: 1.5,
+            fovy: 45.0,
+            znear: 1.0,
+            zfar: 100.0,
+            projection,
+        }
+    }
+
+    #[test]
+    fn depth_spans_the_wgpu_range_once() {
+        for projection in [Projection::Perspective, Projection::Orthographic] {
+            let camera = camera(projection);
+            let view_proj = CameraUniform::projection(&camera)
+                * cgmath::Matrix4::look_at_rh(camera.eye, camera.target, camera.up);
+            assert_eq!(view_proj, camera.build_view_projection_matrix());
+
+            let near = view_proj.transform_point(cgmath::Point3::new(0.0, 0.0, 9.0));
+            let far = view_proj.transform_point(cgmath::Point3::new(0.0, 0.0, -90.0));
+            assert!(near.z.abs() < 1e-5, "{:?}", projection);
+            assert!((far.z - 1.0).abs() < 1e-5, "{:?}", projection);
+        }
+    }
+
+    #[test]
+    fn frame_scales_the_limits_with_the_sphere() {
+        let center = cgmath::Point3::new(1.0, 2.0, 3.0);
+        for radius in [1e-2, 1.0, 1e4] {
+            let mut settings = ControllerSettings::default();
+            let framed = camera(Projection::Perspective)
+                .frame(BoundingSphere { center, radius }, &mut settings);
+
+            assert!((settings.min_distance - radius * 0.01).abs() <= radius * 1e-6);
+            assert!((settings.max_distance - radius * 50.0).abs() <= radius * 1e-6);
+            assert_eq!(framed.target, center);
+
+            // The sphere touches the top and bottom of the view, the narrower side
+            let distance = (framed.eye - center).magnitude();
+            let touching = distance * (framed.fovy.to_radians() / 2.0).sin();
+            assert!((touching - radius).abs() <= radius * 1e-4, "{}", radius);
+            assert!((settings.min_distance..=settings.max_distance).contains(&distance));
+
+            // Nothing is cut off at either limit of the dolly
+            assert!(framed.znear <= settings.min_distance);
+            assert!(framed.zfar >= settings.max_distance + radius);
+        }
     }
 }
@@ -0,0 +1,924 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, TAU};
+
+use cgmath::{InnerSpace, Vector2};
+
+use super::{Camera, Projection};
+
+/// Velocities below it in pixels per second stop the inertia
+const REST_SPEED: f32 = 1.0;
+/// Pixels a press can move and still be a tap
+const TAP_SLOP: f32 = 8.0;
+/// Weight of the newest frame with movement in the velocity of a drag
+const DRAG_SMOOTHING: f32 = 0.5;
+/// Seconds a captured pointer can hold still before its release stops the camera
+const DRAG_REST_TIME: f32 = 0.1;
+
+/// How the camera moves, switching keeps the eye and where it looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    /// Turn around the target and dolly towards it
+    #[default]
+    Orbit,
+    /// Turn around the eye and move with [`CameraController::fly`], scrolling changes the speed
+    Fly,
+}
+
+/// Ways to move in [`CameraMode::Fly`], relative to where the camera looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlyDirection {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    /// Along +y, whatever the pitch
+    Up,
+    Down,
+}
+
+/// Fixed angles to look at the target from, see [`CameraController::view`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewPreset {
+    /// From +z
+    Front,
+    /// From -z
+    Back,
+    /// From -x
+    Left,
+    /// From +x
+    Right,
+    /// From +y, with +z down the screen
+    Top,
+    /// From -y, with +z up the screen
+    Bottom,
+    /// From +x, +y and +z alike
+    Isometric,
+}
+
+impl ViewPreset {
+    pub const ALL: [ViewPreset; 7] = [
+        ViewPreset::Front,
+        ViewPreset::Back,
+        ViewPreset::Left,
+        ViewPreset::Right,
+        ViewPreset::Top,
+        ViewPreset::Bottom,
+        ViewPreset::Isometric,
+    ];
+
+    /// Yaw and pitch of the eye, in radians like those of [`CameraController`].
+    pub fn yaw_pitch(self) -> (f32, f32) {
+        match self {
+            ViewPreset::Front => (FRAC_PI_2, 0.0),
+            ViewPreset::Back => (-FRAC_PI_2, 0.0),
+            ViewPreset::Left => (PI, 0.0),
+            ViewPreset::Right => (0.0, 0.0),
+            ViewPreset::Top => (FRAC_PI_2, FRAC_PI_2),
+            ViewPreset::Bottom => (FRAC_PI_2, -FRAC_PI_2),
+            ViewPreset::Isometric => (FRAC_PI_4, (1.0 / 2f32.sqrt()).atan()),
+        }
+    }
+}
+
+/// What dragging a captured pointer does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DragMode {
+    /// Turn around the target
+    Orbit,
+    /// Move the target in the plane of the view
+    Pan,
+}
+
+/// Speeds and limits of a [`CameraController`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControllerSettings {
+    /// Radians turned per pixel dragged
+    pub orbit_speed: f32,
+    /// Scale of the panning, 1 keeps the point at the depth of the target under the pointer
+    pub pan_speed: f32,
+    /// Exponent of the distance scale per pixel scrolled
+    pub dolly_speed: f32,
+    /// How fast the camera stops after the pointer is released, per second,
+    /// 0 keeps it moving forever and infinity stops it at once
+    pub damping: f32,
+    /// Seconds [`CameraController::view`] takes to turn the camera
+    pub transition_time: f32,
+    /// Radians below the horizon the eye can go, down to -90°
+    pub min_pitch: f32,
+    /// Radians above the horizon the eye can go, up to 90°
+    pub max_pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Distances to the target flown per second in [`CameraMode::Fly`]
+    pub fly_speed: f32,
+}
+
+impl Default for ControllerSettings {
+    fn default() -> Self {
+        Self {
+            orbit_speed: 0.01,
+            pan_speed: 1.0,
+            dolly_speed: 0.001,
+            damping: 8.0,
+            transition_time: 0.5,
+            min_pitch: -FRAC_PI_2,
+            max_pitch: FRAC_PI_2,
+            min_distance: 0.01,
+            max_distance: 5000.0,
+            fly_speed: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CapturedPointer {
+    id: i32,
+    position: (f32, f32),
+    mode: DragMode,
+}
+
+/// Turn of [`CameraController::view`] from one yaw and pitch to another.
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    from: (f32, f32),
+    to: (f32, f32),
+    elapsed: f32,
+}
+
+/// Orbit, pan and dolly of a camera around its target, driven by pointer input.
+///
+/// In [`CameraMode::Fly`] the same input turns the camera around its eye instead,
+/// and the held [`FlyDirection`]s move the eye and the target together.
+///
+/// One captured pointer drags with its [`DragMode`], a second one turns the drag into a pinch
+/// zooming with the spread of the two and panning with their middle.
+///
+/// Input is collected until the next [`CameraController::update`], which also carries
+/// the camera on with the speed of the last drag after the pointers are released.
+/// It only knows about pixels and seconds, the viewer feeds it the events of the page.
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    pub settings: ControllerSettings,
+    mode: CameraMode,
+    target: cgmath::Point3<f32>,
+    /// Radians around +y, from +x towards +z
+    yaw: f32,
+    /// Radians above the horizon
+    pitch: f32,
+    distance: f32,
+    /// Vertical field of view of the camera, in degrees
+    fovy: f32,
+    projection: Projection,
+    /// The projection changed since the last update
+    reprojected: bool,
+    /// Pixels of the view, in the unit of the pointer positions
+    viewport: (f32, f32),
+
+    /// At most two, the first one drags and both pinch
+    pointers: Vec<CapturedPointer>,
+    /// Where the press of the only pointer started, `None` once it moved or pinched
+    press: Option<(f32, f32)>,
+    /// Pixels dragged since the last update
+    pending_orbit: Vector2<f32>,
+    pending_pan: Vector2<f32>,
+    pending_dolly: f32,
+    /// Pixels per second of the drag, smoothed over the frames it moved in
+    orbit_velocity: Vector2<f32>,
+    pan_velocity: Vector2<f32>,
+    /// Seconds the drag held still
+    drag_rest: f32,
+    /// Held [`FlyDirection`]s, by their index
+    flying: [bool; 6],
+    transition: Option<Transition>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self::new(ControllerSettings::default())
+    }
+}
+
+impl CameraController {
+    pub fn new(settings: ControllerSettings) -> Self {
+        Self {
+            settings,
+            mode: CameraMode::Orbit,
+            target: cgmath::Point3::new(0.0, 0.0, 0.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 1.0,
+            fovy: 45.0,
+            projection: Projection::Perspective,
+            reprojected: false,
+            viewport: (1.0, 1.0),
+            pointers: Vec::with_capacity(2),
+            press: None,
+            pending_orbit: Vector2::new(0.0, 0.0),
+            pending_pan: Vector2::new(0.0, 0.0),
+            pending_dolly: 0.0,
+            orbit_velocity: Vector2::new(0.0, 0.0),
+            pan_velocity: Vector2::new(0.0, 0.0),
+            drag_rest: 0.0,
+            flying: [false; 6],
+            transition: None,
+        }
+    }
+
+    /// Continue from the eye and target of `camera`, stopping the inertia.
+    pub fn look_from(&mut self, camera: &Camera) {
+        self.follow(camera);
+        self.stop();
+    }
+
+    /// Move to the eye and target of `camera`, the input not applied yet and the inertia carry on.
+    pub fn follow(&mut self, camera: &Camera) {
+        let offset = camera.eye - camera.target;
+        let distance = offset.magnitude();
+
+        self.target = camera.target;
+        self.fovy = camera.fovy;
+        self.projection = camera.projection;
+        if distance > f32::EPSILON {
+            self.distance = distance;
+            self.pitch = (offset.y / distance).clamp(-1.0, 1.0).asin();
+            // Straight above or below, the up of the camera tells where the yaw faces
+            let horizontal = if offset.x.hypot(offset.z) > distance * 1e-4 {
+                offset
+            } else {
+                -camera.up * self.pitch.signum()
+            };
+            self.yaw = horizontal.z.atan2(horizontal.x);
+        }
+        self.clamp();
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switch between orbiting and flying, the camera stays where it is.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.stop();
+        }
+    }
+
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Project orthographically or in perspective, the view keeps its size around the target.
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.reprojected |= self.projection != projection;
+        self.projection = projection;
+    }
+
+    /// Turn the camera around the target to look from `preset`, over `settings.transition_time`.
+    /// Dragging or [`CameraController::stop`] cancel the turn.
+    pub fn view(&mut self, preset: ViewPreset) {
+        let (yaw, pitch) = preset.yaw_pitch();
+        // The shorter way around
+        let turn = (yaw - self.yaw + PI).rem_euclid(TAU) - PI;
+        self.transition = Some(Transition {
+            from: (self.yaw, self.pitch),
+            to: (self.yaw + turn, pitch),
+            elapsed: 0.0,
+        });
+        self.orbit_velocity = Vector2::new(0.0, 0.0);
+    }
+
+    /// Drop the input not applied yet, the inertia, the held directions and the turn to a preset.
+    pub fn stop(&mut self) {
+        self.pending_orbit = Vector2::new(0.0, 0.0);
+        self.pending_pan = Vector2::new(0.0, 0.0);
+        self.pending_dolly = 0.0;
+        self.orbit_velocity = Vector2::new(0.0, 0.0);
+        self.pan_velocity = Vector2::new(0.0, 0.0);
+        self.flying = [false; 6];
+        self.transition = None;
+    }
+
+    pub fn target(&self) -> cgmath::Point3<f32> {
+        self.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn eye(&self) -> cgmath::Point3<f32> {
+        self.target + self.direction() * self.distance
+    }
+
+    /// Unit vector from the target to the eye.
+    fn direction(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.sin(),
+        )
+    }
+
+    /// Up of the view, defined straight above or below the target as well.
+    fn up(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(
+            -self.pitch.sin() * self.yaw.cos(),
+            self.pitch.cos(),
+            -self.pitch.sin() * self.yaw.sin(),
+        )
+    }
+
+    /// Right of the view, level with the horizon.
+    fn right(&self) -> cgmath::Vector3<f32> {
+        cgmath::Vector3::new(self.yaw.sin(), 0.0, -self.yaw.cos())
+    }
+
+    /// Size of the view in the unit of the pointer positions, CSS pixels on a page.
+    pub fn set_viewport(&mut self, width: f32, height: f32) {
+        self.viewport = (width.max(1.0), height.max(1.0));
+    }
+
+    /// Start dragging with the pointer `id` at `x, y` pixels, the pointer is captured
+    /// until [`CameraController::pointer_up`] and stops the inertia.
+    /// A second pointer starts a pinch, `mode` only matters for the first one.
+    ///
+    /// Returns false if two other pointers are already captured.
+    pub fn pointer_down(&mut self, id: i32, x: f32, y: f32, mode: DragMode) -> bool {
+        if let Some(pointer) = self.pointers.iter_mut().find(|p| p.id == id) {
+            pointer.position = (x, y);
+            return true;
+        }
+        if self.pointers.len() >= 2 {
+            return false;
+        }
+
+        self.press = self.pointers.is_empty().then_some((x, y));
+        self.pointers.push(CapturedPointer {
+            id,
+            position: (x, y),
+            mode,
+        });
+        self.orbit_velocity = Vector2::new(0.0, 0.0);
+        self.pan_velocity = Vector2::new(0.0, 0.0);
+        self.drag_rest = 0.0;
+        true
+    }
+
+    /// Move the captured pointer `id` to `x, y`, other pointers are ignored.
+    pub fn pointer_move(&mut self, id: i32, x: f32, y: f32) {
+        let index = match self.pointers.iter().position(|p| p.id == id) {
+            Some(index) => index,
+            None => return,
+        };
+        let previous = self.pointers[index].position;
+        let other = (self.pointers.len() == 2).then(|| self.pointers[1 - index].position);
+
+        match other {
+            // Pinch, the middle pans and the spread zooms
+            Some(other) => {
+                let middle = |p: (f32, f32)| ((p.0 + other.0) * 0.5, (p.1 + other.1) * 0.5);
+                let spread = |p: (f32, f32)| Vector2::new(p.0 - other.0, p.1 - other.1).magnitude();
+                let (from, to) = (middle(previous), middle((x, y)));
+                self.pan(to.0 - from.0, to.1 - from.1);
+
+                let (from, to) = (spread(previous), spread((x, y)));
+                if from > 0.0 && to > 0.0 && self.settings.dolly_speed > 0.0 {
+                    self.dolly((from / to).ln() / self.settings.dolly_speed);
+                }
+            }
+            None => {
+                let (dx, dy) = (x - previous.0, y - previous.1);
+                match self.pointers[index].mode {
+                    DragMode::Orbit => self.orbit(dx, dy),
+                    DragMode::Pan => self.pan(dx, dy),
+                }
+            }
+        }
+        self.pointers[index].position = (x, y);
+
+        if let Some(press) = self.press {
+            if Vector2::new(x - press.0, y - press.1).magnitude() > TAP_SLOP {
+                self.press = None;
+            }
+        }
+    }
+
+    /// Release the pointer `id`, the camera keeps the speed of the drag and slows down.
+    ///
+    /// Returns true if the pointer was pressed alone and released where it went down, a tap.
+    pub fn pointer_up(&mut self, id: i32) -> bool {
+        let index = match self.pointers.iter().position(|p| p.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.pointers.remove(index);
+
+        // The finger left of a pinch doesn't tap
+        if !self.pointers.is_empty() {
+            self.press = None;
+        }
+        self.press.take().is_some() && self.pointers.is_empty()
+    }
+
+    /// If the pointer `id` drags the camera.
+    pub fn is_captured(&self, id: i32) -> bool {
+        self.pointers.iter().any(|p| p.id == id)
+    }
+
+    /// Glide the target to the point at its depth shown at `x, y`, bringing it to the middle of the view.
+    pub fn refocus(&mut self, x: f32, y: f32) {
+        let offset = Vector2::new(self.viewport.0 * 0.5 - x, self.viewport.1 * 0.5 - y);
+
+        // The inertia covers the speed divided by the damping
+        if self.settings.damping > 0.0 && self.settings.damping.is_finite() {
+            self.pan_velocity = offset * self.settings.damping;
+        } else {
+            self.pan(offset.x, offset.y);
+        }
+    }
+
+    /// Turn around the target by pixels dragged, right and down turn the scene with the pointer.
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.pending_orbit += Vector2::new(dx, dy);
+    }
+
+    /// Move the target by pixels dragged, the scene follows the pointer.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.pending_pan += Vector2::new(dx, dy);
+    }
+
+    /// Move towards the target by pixels scrolled, positive moves away.
+    /// In [`CameraMode::Fly`] it slows the flight down instead, negative speeds it up.
+    pub fn dolly(&mut self, delta: f32) {
+        self.pending_dolly += delta;
+    }
+
+    /// Start or stop moving towards `direction` in [`CameraMode::Fly`], until released.
+    pub fn fly(&mut self, direction: FlyDirection, held: bool) {
+        self.flying[direction as usize] = held;
+    }
+
+    /// If the camera moves on the next update.
+    pub fn is_moving(&self) -> bool {
+        self.has_pending()
+            || self.transition.is_some()
+            || (self.mode == CameraMode::Fly && self.flying.contains(&true))
+            || self.orbit_velocity.magnitude() > REST_SPEED
+            || self.pan_velocity.magnitude() > REST_SPEED
+    }
+
+    fn has_pending(&self) -> bool {
+        self.pending_orbit != Vector2::new(0.0, 0.0)
+            || self.pending_pan != Vector2::new(0.0, 0.0)
+            || self.pending_dolly != 0.0
+            || self.reprojected
+    }
+
+    /// Apply the input since the last update and `delta` seconds of inertia,
+    /// returns false if the camera didn't move.
+    pub fn update(&mut self, delta: f32) -> bool {
+        let (orbit, pan) = if !self.pointers.is_empty() {
+            // Dragging, the speed of the recent moves carries on after the release.
+            // Pointers move less often than frames are drawn, frames without moves are skipped
+            if self.pending_orbit != Vector2::new(0.0, 0.0)
+                || self.pending_pan != Vector2::new(0.0, 0.0)
+            {
+                if delta > 0.0 {
+                    self.orbit_velocity = smooth(self.orbit_velocity, self.pending_orbit / delta);
+                    self.pan_velocity = smooth(self.pan_velocity, self.pending_pan / delta);
+                }
+                self.drag_rest = 0.0;
+            } else {
+                self.drag_rest += delta;
+                if self.drag_rest >= DRAG_REST_TIME {
+                    self.orbit_velocity = Vector2::new(0.0, 0.0);
+                    self.pan_velocity = Vector2::new(0.0, 0.0);
+                }
+            }
+            (self.pending_orbit, self.pending_pan)
+        } else {
+            // Distance covered while the speed decays over the frame
+            let damping = self.settings.damping;
+            let decay = (-damping * delta).exp();
+            let travel = if damping > 0.0 {
+                (1.0 - decay) / damping
+            } else {
+                delta
+            };
+            let (orbit, pan) = (self.orbit_velocity * travel, self.pan_velocity * travel);
+
+            self.orbit_velocity *= decay;
+            self.pan_velocity *= decay;
+            if self.orbit_velocity.magnitude() <= REST_SPEED {
+                self.orbit_velocity = Vector2::new(0.0, 0.0);
+            }
+            if self.pan_velocity.magnitude() <= REST_SPEED {
+                self.pan_velocity = Vector2::new(0.0, 0.0);
+            }
+            (self.pending_orbit + orbit, self.pending_pan + pan)
+        };
+        let dolly = self.pending_dolly;
+        let reprojected = self.reprojected;
+        self.pending_orbit = Vector2::new(0.0, 0.0);
+        self.pending_pan = Vector2::new(0.0, 0.0);
+        self.pending_dolly = 0.0;
+        self.reprojected = false;
+
+        // Turning by hand takes over from a preset
+        if orbit != Vector2::new(0.0, 0.0) || !self.pointers.is_empty() {
+            self.transition = None;
+        }
+
+        let flight = match self.mode {
+            CameraMode::Fly => self.flight(),
+            CameraMode::Orbit => cgmath::Vector3::new(0.0, 0.0, 0.0),
+        };
+        if orbit == Vector2::new(0.0, 0.0)
+            && pan == Vector2::new(0.0, 0.0)
+            && dolly == 0.0
+            && flight == cgmath::Vector3::new(0.0, 0.0, 0.0)
+            && self.transition.is_none()
+            && !reprojected
+        {
+            return false;
+        }
+
+        // Flying turns around the eye, it stays where it is
+        let eye = self.eye();
+        if let Some(transition) = &mut self.transition {
+            transition.elapsed += delta;
+            let t = match self.settings.transition_time {
+                time if time > 0.0 => (transition.elapsed / time).min(1.0),
+                _ => 1.0,
+            };
+            let t = t * t * (3.0 - 2.0 * t);
+            self.yaw = transition.from.0 + (transition.to.0 - transition.from.0) * t;
+            self.pitch = transition.from.1 + (transition.to.1 - transition.from.1) * t;
+            if t >= 1.0 {
+                self.transition = None;
+            }
+        }
+        self.yaw = (self.yaw + orbit.x * self.settings.orbit_speed) % TAU;
+        self.pitch += orbit.y * self.settings.orbit_speed;
+        self.clamp();
+        if self.mode == CameraMode::Fly {
+            self.target = eye - self.direction() * self.distance;
+        }
+
+        let forward = -self.direction();
+        let right = self.right();
+        let up = self.up();
+        // World units per pixel at the depth of the target
+        let scale = 2.0 * self.distance * (self.fovy.to_radians() * 0.5).tan() / self.viewport.1
+            * self.settings.pan_speed;
+        self.target += (up * pan.y - right * pan.x) * scale;
+
+        match self.mode {
+            CameraMode::Orbit => self.distance *= (dolly * self.settings.dolly_speed).exp(),
+            CameraMode::Fly => {
+                self.settings.fly_speed *= (-dolly * self.settings.dolly_speed).exp();
+                self.target +=
+                    (forward * flight.z + right * flight.x + cgmath::Vector3::unit_y() * flight.y)
+                        * self.settings.fly_speed
+                        * self.distance
+                        * delta;
+            }
+        }
+
+        self.clamp();
+        true
+    }
+
+    /// Held directions as right, up and forward, each -1, 0 or 1.
+    fn flight(&self) -> cgmath::Vector3<f32> {
+        let axis = |positive: FlyDirection, negative: FlyDirection| {
+            self.flying[positive as usize] as i32 as f32
+                - self.flying[negative as usize] as i32 as f32
+        };
+        cgmath::Vector3::new(
+            axis(FlyDirection::Right, FlyDirection::Left),
+            axis(FlyDirection::Up, FlyDirection::Down),
+            axis(FlyDirection::Forward, FlyDirection::Backward),
+        )
+    }
+
+    fn clamp(&mut self) {
+        let settings = &self.settings;
+        self.pitch = self.pitch.clamp(
+            settings.min_pitch.max(-FRAC_PI_2),
+            settings.max_pitch.min(FRAC_PI_2),
+        );
+        self.distance = self
+            .distance
+            .clamp(settings.min_distance, settings.max_distance);
+    }
+
+    /// `camera` seen from the eye of the controller, looking at its target.
+    pub fn apply(&self, camera: Camera) -> Camera {
+        Camera {
+            eye: self.eye(),
+            target: self.target,
+            up: self.up(),
+            projection: self.projection,
+            ..camera
+        }
+    }
+}
+
+/// Blend the speed of a frame into the velocity of a drag, the first one is taken as is.
+fn smooth(velocity: Vector2<f32>, speed: Vector2<f32>) -> Vector2<f32> {
+    if velocity == Vector2::new(0.0, 0.0) {
+        speed
+    } else {
+        velocity + (speed - velocity) * DRAG_SMOOTHING
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn camera(eye: [f32; 3], target: [f32; 3]) -> Camera {
+        Camera {
+            eye: eye.into(),
+            target: target.into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect: 1.0,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+        }
+    }
+
+    /// Looking from +x at the origin, 10 away, through a 100 pixels high view.
+    fn controller() -> CameraController {
+        let mut controller = CameraController::default();
+        controller.look_from(&camera([10.0, 0.0, 0.0], [0.0, 0.0, 0.0]));
+        controller.set_viewport(100.0, 100.0);
+        controller
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < EPSILON, "{} != {}", a, b);
+    }
+
+    fn assert_points_close(a: cgmath::Point3<f32>, b: cgmath::Point3<f32>) {
+        assert!((a - b).magnitude() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    /// Drag the pointer 0 by `dx, dy` in one frame of `delta` seconds.
+    fn drag(controller: &mut CameraController, dx: f32, dy: f32, delta: f32) {
+        controller.pointer_move(0, dx, dy);
+        controller.update(delta);
+    }
+
+    #[test]
+    fn look_from_round_trip() {
+        let mut controller = CameraController::default();
+        let camera = camera([3.0, 4.0, -5.0], [1.0, 1.0, 1.0]);
+        controller.look_from(&camera);
+
+        let applied = controller.apply(camera);
+        assert_points_close(applied.eye, camera.eye);
+        assert_points_close(applied.target, camera.target);
+        assert_close(
+            controller.distance(),
+            (camera.eye - camera.target).magnitude(),
+        );
+        assert!(applied.up.dot(applied.target - applied.eye).abs() < EPSILON);
+    }
+
+    #[test]
+    fn look_from_straight_above_keeps_the_up() {
+        let mut controller = CameraController::default();
+        let mut camera = camera([0.0, 5.0, 0.0], [0.0, 0.0, 0.0]);
+        camera.up = -cgmath::Vector3::unit_z();
+        controller.look_from(&camera);
+
+        let applied = controller.apply(camera);
+        assert_points_close(applied.eye, camera.eye);
+        assert!((applied.up - camera.up).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn orbit_turns_around_the_target() {
+        let mut controller = controller();
+        controller.orbit(100.0, 50.0);
+        assert!(controller.update(0.016));
+
+        assert_close(controller.yaw(), 100.0 * controller.settings.orbit_speed);
+        assert_close(controller.pitch(), 50.0 * controller.settings.orbit_speed);
+        assert_close(controller.distance(), 10.0);
+        assert_points_close(controller.target(), cgmath::Point3::new(0.0, 0.0, 0.0));
+        assert!(!controller.update(0.016));
+    }
+
+    #[test]
+    fn pitch_stays_within_the_limits() {
+        let mut controller = controller();
+        controller.orbit(0.0, 1e4);
+        controller.update(0.016);
+        assert_close(controller.pitch(), FRAC_PI_2);
+
+        controller.settings.min_pitch = -0.5;
+        controller.orbit(0.0, -1e4);
+        controller.update(0.016);
+        assert_close(controller.pitch(), -0.5);
+    }
+
+    #[test]
+    fn pan_keeps_the_target_under_the_pointer() {
+        let mut controller = controller();
+        // The whole height of the view at the depth of the target
+        let height = 2.0 * 10.0 * (45f32.to_radians() / 2.0).tan();
+        controller.pan(0.0, 100.0);
+        controller.update(0.016);
+
+        assert_points_close(controller.target(), cgmath::Point3::new(0.0, height, 0.0));
+        assert_close(controller.distance(), 10.0);
+
+        // Looking down -x the right of the view is -z, the target moves the other way
+        controller.pan(50.0, 0.0);
+        controller.update(0.016);
+        assert_points_close(
+            controller.target(),
+            cgmath::Point3::new(0.0, height, height * 0.5),
+        );
+    }
+
+    #[test]
+    fn dolly_scales_the_distance_within_the_limits() {
+        let mut controller = controller();
+        controller.dolly(100.0);
+        controller.update(0.016);
+        assert_close(
+            controller.distance(),
+            10.0 * (100.0 * controller.settings.dolly_speed).exp(),
+        );
+
+        controller.settings.max_distance = 20.0;
+        controller.dolly(1e5);
+        controller.update(0.016);
+        assert_close(controller.distance(), 20.0);
+
+        controller.settings.min_distance = 2.0;
+        controller.dolly(-1e5);
+        controller.update(0.016);
+        assert_close(controller.distance(), 2.0);
+    }
+
+    #[test]
+    fn inertia_decays_after_the_release() {
+        let mut controller = controller();
+        controller.pointer_down(0, 0.0, 0.0, DragMode::Orbit);
+        drag(&mut controller, 10.0, 0.0, 0.01);
+        let yaw_at_release = controller.yaw();
+        assert!(!controller.pointer_up(0));
+        assert!(controller.is_moving());
+
+        let mut frames = 0;
+        let mut yaw = yaw_at_release;
+        while controller.update(0.01) {
+            assert!(controller.yaw() > yaw);
+            yaw = controller.yaw();
+            frames += 1;
+            assert!(frames < 10_000, "the inertia never stops");
+        }
+        assert!(!controller.is_moving());
+
+        // 1000 pixels per second, covering the speed divided by the damping
+        let travel = 1000.0 / controller.settings.damping * controller.settings.orbit_speed;
+        assert!((yaw - yaw_at_release - travel).abs() < travel * 0.01);
+    }
+
+    #[test]
+    fn drag_velocity_survives_frames_without_moves() {
+        let mut controller = controller();
+        controller.pointer_down(0, 0.0, 0.0, DragMode::Orbit);
+        drag(&mut controller, 10.0, 0.0, 0.01);
+        // The pointer didn't move during this frame
+        controller.update(0.01);
+        drag(&mut controller, 20.0, 0.0, 0.01);
+        controller.pointer_up(0);
+
+        let yaw = controller.yaw();
+        controller.update(0.01);
+        assert!(controller.yaw() > yaw);
+    }
+
+    #[test]
+    fn drag_held_still_releases_without_inertia() {
+        let mut controller = controller();
+        controller.pointer_down(0, 0.0, 0.0, DragMode::Orbit);
+        drag(&mut controller, 10.0, 0.0, 0.01);
+        for _ in 0..20 {
+            controller.update(0.01);
+        }
+        controller.pointer_up(0);
+
+        assert!(!controller.is_moving());
+        assert!(!controller.update(0.01));
+    }
+
+    #[test]
+    fn stop_drops_input_inertia_and_turns() {
+        let mut controller = controller();
+        controller.pointer_down(0, 0.0, 0.0, DragMode::Pan);
+        drag(&mut controller, 10.0, 0.0, 0.01);
+        controller.pointer_up(0);
+        controller.orbit(5.0, 5.0);
+        controller.dolly(5.0);
+        controller.fly(FlyDirection::Forward, true);
+        controller.view(ViewPreset::Top);
+        assert!(controller.is_moving());
+
+        controller.stop();
+        assert!(!controller.is_moving());
+        let eye = controller.eye();
+        assert!(!controller.update(0.01));
+        assert_points_close(controller.eye(), eye);
+    }
+
+    #[test]
+    fn follow_keeps_the_inertia() {
+        let mut controller = controller();
+        controller.pointer_down(0, 0.0, 0.0, DragMode::Orbit);
+        drag(&mut controller, 10.0, 0.0, 0.01);
+        controller.pointer_up(0);
+
+        controller.follow(&camera([0.0, 0.0, 10.0], [0.0, 0.0, 0.0]));
+        assert!(controller.is_moving());
+        assert_close(controller.yaw(), FRAC_PI_2);
+
+        controller.look_from(&camera([0.0, 0.0, 10.0], [0.0, 0.0, 0.0]));
+        assert!(!controller.is_moving());
+    }
+
+    #[test]
+    fn taps_and_pinches() {
+        let mut controller = controller();
+        controller.pointer_down(0, 10.0, 10.0, DragMode::Orbit);
+        controller.pointer_move(0, 12.0, 10.0);
+        assert!(controller.pointer_up(0));
+
+        controller.pointer_down(0, 10.0, 10.0, DragMode::Orbit);
+        controller.pointer_move(0, 30.0, 10.0);
+        assert!(!controller.pointer_up(0));
+
+        // Spreading two fingers to twice their distance halves the distance
+        controller.pointer_down(0, 40.0, 50.0, DragMode::Orbit);
+        controller.pointer_down(1, 60.0, 50.0, DragMode::Orbit);
+        assert!(!controller.pointer_down(2, 0.0, 0.0, DragMode::Orbit));
+        controller.pointer_move(1, 80.0, 50.0);
+        controller.pointer_move(0, 20.0, 50.0);
+        controller.update(0.01);
+        assert_close(controller.distance(), 10.0 / 3.0);
+        assert!(!controller.pointer_up(1));
+        assert!(!controller.pointer_up(0));
+    }
+
+    #[test]
+    fn view_turns_to_the_preset() {
+        let mut controller = controller();
+        controller.view(ViewPreset::Front);
+        for _ in 0..100 {
+            controller.update(0.01);
+        }
+
+        let (yaw, pitch) = ViewPreset::Front.yaw_pitch();
+        assert_close(controller.yaw(), yaw);
+        assert_close(controller.pitch(), pitch);
+        assert_points_close(controller.eye(), cgmath::Point3::new(0.0, 0.0, 10.0));
+        assert!(!controller.is_moving());
+    }
+
+    #[test]
+    fn fly_moves_the_eye_and_the_target() {
+        let mut controller = controller();
+        controller.set_mode(CameraMode::Fly);
+        controller.fly(FlyDirection::Forward, true);
+        controller.update(1.0);
+
+        // Half the distance to the target per second, towards -x
+        let flown = controller.settings.fly_speed * 10.0;
+        assert_points_close(controller.target(), cgmath::Point3::new(-flown, 0.0, 0.0));
+        assert_points_close(
+            controller.eye(),
+            cgmath::Point3::new(10.0 - flown, 0.0, 0.0),
+        );
+
+        controller.fly(FlyDirection::Forward, false);
+        assert!(!controller.update(1.0));
+    }
+}
@@ -0,0 +1,393 @@
+use wgpu::util::DeviceExt;
+
+use crate::{main_player::error::PlayerErrorResult, requests};
+
+use super::shader;
+
+/// Width and height of a face of the environment cube
+const CUBE_SIZE: u32 = 256;
+const IRRADIANCE_SIZE: u32 = 32;
+/// Size of the first mip of the prefiltered cube, rougher surfaces read smaller mips
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_MIPS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 128;
+/// Filterable and renderable on WebGL 2 with `EXT_color_buffer_float`
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// What a bake pass renders, one face of a cube or the lookup table.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeParams {
+    face: u32,
+    roughness: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 2],
+}
+
+/// Image based lighting, precomputed from an equirectangular environment.
+pub(crate) struct Environment {
+    /// Kept alive for the views
+    _textures: Vec<wgpu::Texture>,
+    /// Radiance from every direction, drawn by the environment background
+    pub(crate) cube: wgpu::TextureView,
+    /// Cosine weighted radiance around every normal, for diffuse light
+    pub(crate) irradiance: wgpu::TextureView,
+    /// Radiance blurred by ever rougher GGX lobes along the mips, for specular light
+    pub(crate) prefiltered: wgpu::TextureView,
+    /// Scale and bias of the fresnel term, by n·v and roughness
+    pub(crate) brdf_lut: wgpu::TextureView,
+    pub(crate) sampler: wgpu::Sampler,
+    /// Mips of `prefiltered`, 0 for the neutral environment of states without one
+    pub(crate) mip_count: u32,
+}
+
+impl Environment {
+    /// Black maps, bound while no environment is loaded.
+    pub(crate) fn neutral(device: &wgpu::Device) -> Self {
+        let cube = create_cube(device, "Neutral Cube", 1, 1);
+        let irradiance = create_cube(device, "Neutral Irradiance", 1, 1);
+        let prefiltered = create_cube(device, "Neutral Prefiltered", 1, 1);
+        let brdf_lut = create_texture(device, "Neutral BRDF LUT", 1);
+
+        Self {
+            cube: cube_view(&cube),
+            irradiance: cube_view(&irradiance),
+            prefiltered: cube_view(&prefiltered),
+            brdf_lut: brdf_lut.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler: create_sampler(device),
+            mip_count: 0,
+            _textures: vec![cube, irradiance, prefiltered, brdf_lut],
+        }
+    }
+
+    /// Bake the maps of an equirectangular image on the GPU.
+    pub(crate) async fn from_hdr(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        hdr: requests::HdrImage,
+    ) -> PlayerErrorResult<Self> {
+        let equirect = upload_equirect(device, queue, hdr);
+        let equirect_view = equirect.create_view(&wgpu::TextureViewDescriptor::default());
+        let equirect_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Equirect Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            // 32 bit floats are not filterable everywhere
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let sampler = create_sampler(device);
+
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let equirect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+            ],
+            label: Some("equirect_bind_group_layout"),
+        });
+        let cube_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                params_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("cube_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("IBL Shader", "ibl.wgsl").await?;
+        let pipeline = |entry_point, layout| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("IBL Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            shader.create_fullscreen_pipeline(
+                device,
+                &layout,
+                entry_point,
+                FORMAT,
+                None,
+                shader::PipelineOptions::default(),
+            )
+        };
+        let equirect_pipeline = pipeline("fs_equirect", &equirect_layout);
+        let irradiance_pipeline = pipeline("fs_irradiance", &cube_layout);
+        let prefilter_pipeline = pipeline("fs_prefilter", &cube_layout);
+        let brdf_pipeline = pipeline("fs_brdf", &cube_layout);
+
+        let cube = create_cube(device, "Environment Cube", CUBE_SIZE, 1);
+        let cube_source = cube_view(&cube);
+        let irradiance = create_cube(device, "Irradiance", IRRADIANCE_SIZE, 1);
+        let prefiltered = create_cube(device, "Prefiltered", PREFILTERED_SIZE, PREFILTERED_MIPS);
+        let brdf_lut = create_texture(device, "BRDF LUT", BRDF_LUT_SIZE);
+
+        // Every pass has its own parameters, writes to one buffer would all land before the first.
+        // The source texture and sampler follow the parameters in the bindings of `layout`
+        let bind_group = |layout,
+                          source_binding,
+                          source: (&wgpu::TextureView, &wgpu::Sampler),
+                          face,
+                          roughness| {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Bake Params Buffer"),
+                contents: bytemuck::cast_slice(&[BakeParams {
+                    face,
+                    roughness,
+                    _padding: [0; 2],
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: source_binding,
+                        resource: wgpu::BindingResource::TextureView(source.0),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: source_binding + 1,
+                        resource: wgpu::BindingResource::Sampler(source.1),
+                    },
+                ],
+                label: Some("bake_bind_group"),
+            })
+        };
+        let equirect_source = (&equirect_view, &equirect_sampler);
+        let cube_source = (&cube_source, &sampler);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("IBL Encoder"),
+        });
+        // The cube first, the other maps are sampled from it
+        for face in 0..6 {
+            let target = face_view(&cube, face, 0);
+            let bind_group = bind_group(&equirect_layout, 1, equirect_source, face, 0.0);
+            bake(&mut encoder, &target, &equirect_pipeline, &bind_group);
+        }
+        for face in 0..6 {
+            let target = face_view(&irradiance, face, 0);
+            let bind_group = bind_group(&cube_layout, 3, cube_source, face, 0.0);
+            bake(&mut encoder, &target, &irradiance_pipeline, &bind_group);
+        }
+        for mip in 0..PREFILTERED_MIPS {
+            let roughness = mip as f32 / (PREFILTERED_MIPS - 1) as f32;
+            for face in 0..6 {
+                let target = face_view(&prefiltered, face, mip);
+                let bind_group = bind_group(&cube_layout, 3, cube_source, face, roughness);
+                bake(&mut encoder, &target, &prefilter_pipeline, &bind_group);
+            }
+        }
+        let target = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = bind_group(&cube_layout, 3, cube_source, 0, 0.0);
+        bake(&mut encoder, &target, &brdf_pipeline, &bind_group);
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(Self {
+            cube: cube_view(&cube),
+            irradiance: cube_view(&irradiance),
+            prefiltered: cube_view(&prefiltered),
+            brdf_lut: target,
+            sampler,
+            mip_count: PREFILTERED_MIPS,
+            _textures: vec![cube, irradiance, prefiltered, brdf_lut],
+        })
+    }
+}
+
+/// 32 bit float texture of the image, shrunk to what the device takes.
+fn upload_equirect(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    hdr: requests::HdrImage,
+) -> wgpu::Texture {
+    let (mut width, mut height) = hdr.dimensions();
+    let mut pixels = hdr.pixels().to_vec();
+
+    let max_size = device.limits().max_texture_dimension_2d;
+    while width > max_size || height > max_size {
+        let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+        pixels = (0..half_height)
+            .flat_map(|y| (0..half_width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let mut sum = [0.0; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sx = (2 * x + dx).min(width - 1);
+                    let sy = (2 * y + dy).min(height - 1);
+                    let pixel = pixels[(sy * width + sx) as usize];
+                    for (s, p) in sum.iter_mut().zip(pixel) {
+                        *s += p / 4.0;
+                    }
+                }
+                sum
+            })
+            .collect();
+        (width, height) = (half_width, half_height);
+    }
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Equirect"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba32Float,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    });
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        bytemuck::cast_slice(&pixels),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(16 * width),
+            rows_per_image: std::num::NonZeroU32::new(height),
+        },
+        size,
+    );
+
+    texture
+}
+
+fn create_cube(device: &wgpu::Device, label: &str, size: u32, mips: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count: mips,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    })
+}
+
+fn create_texture(device: &wgpu::Device, label: &str, size: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    })
+}
+
+fn create_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Environment Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+fn cube_view(texture: &wgpu::Texture) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::Cube),
+        ..Default::default()
+    })
+}
+
+/// One mip of one face, faces in the order +x, -x, +y, -y, +z, -z.
+fn face_view(texture: &wgpu::Texture, face: u32, mip: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_mip_level: mip,
+        mip_level_count: std::num::NonZeroU32::new(1),
+        base_array_layer: face,
+        array_layer_count: std::num::NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+fn bake(
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+) {
+    let mut bake_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Bake Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    bake_pass.set_pipeline(pipeline);
+    bake_pass.set_bind_group(0, bind_group, &[]);
+    bake_pass.draw(0..3, 0..1);
+}
@@ -1,19 +1,4 @@
-#[derive(Debug, Clone, PartialEq)]
-pub struct Instance {
-    pub position: cgmath::Vector3<f32>,
-    pub rotation: cgmath::Quaternion<f32>,
-}
-
-impl Instance {
-    pub fn to_raw(&self) -> InstanceRaw {
-        InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
-            normal: cgmath::Matrix3::from(self.rotation).into(),
-        }
-    }
-}
+use cgmath::{Matrix, SquareMatrix};
 
 #[repr(C)]
 #[derive(Debug, PartialEq,Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -22,10 +7,21 @@ pub struct InstanceRaw {
     normal: [[f32; 3]; 3],
 }
 
-pub const NUM_INSTANCES_PER_ROW: u32 = 1;
-pub const SPACE_BETWEEN: f32 = 0.0;
-
 impl InstanceRaw {
+    /// Instance placed by the world matrix of a scene node.
+    pub fn from_matrix(model: cgmath::Matrix4<f32>) -> Self {
+        let normal =
+            cgmath::Matrix3::from_cols(model.x.truncate(), model.y.truncate(), model.z.truncate())
+                .invert()
+                .unwrap_or_else(cgmath::Matrix3::identity)
+                .transpose();
+
+        Self {
+            model: model.into(),
+            normal: normal.into(),
+        }
+    }
+
     pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -1,14 +1,16 @@
 use std::ops::Range;
 
-use super::model::{mesh::Mesh, Model};
+use bytemuck::Zeroable;
+use cgmath::{InnerSpace, SquareMatrix};
+use serde::Deserialize;
+
+use super::{
+    camera,
+    model::{mesh::Mesh, Model},
+    shadow,
+};
 
 pub(crate) trait DrawLight<'a> {
-    fn draw_light_mesh(
-        &mut self,
-        mesh: &'a Mesh,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
     fn draw_light_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
@@ -16,13 +18,6 @@ pub(crate) trait DrawLight<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
-
-    fn draw_light_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
     fn draw_light_model_instanced(
         &mut self,
         model: &'a Model,
@@ -36,15 +31,6 @@ impl<'a, 'b> DrawLight<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a,
 {
-    fn draw_light_mesh(
-        &mut self,
-        mesh: &'b Mesh,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_light_mesh_instanced(mesh, 0..1, camera_bind_group, light_bind_group);
-    }
-
     fn draw_light_mesh_instanced(
         &mut self,
         mesh: &'b Mesh,
@@ -59,14 +45,6 @@ where
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
 
-    fn draw_light_model(
-        &mut self,
-        model: &'b Model,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_light_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
-    }
     fn draw_light_model_instanced(
         &mut self,
         model: &'b Model,
@@ -85,13 +63,223 @@ where
     }
 }
 
+/// Most lights the shaders accumulate, the rest is ignored
+pub const MAX_LIGHTS: usize = 16;
+
+/// Near plane of the shadows of spot lights, their range has to reach past it
+pub(crate) const SPOT_SHADOW_NEAR: f32 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightKind {
+    /// Shines from `position` in all directions
+    #[default]
+    Point,
+    /// Shines along `direction` from infinitely far away, like the sun
+    Directional,
+    /// Shines from `position` along `direction` inside a cone
+    Spot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub position: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>,
+    /// Linear rgb
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance where point and spot lights fade out, `None` reaches infinitely far
+    pub range: Option<f32>,
+    /// Angle from the axis of a spot light to where it starts to fade, radians
+    pub inner_cone: f32,
+    /// Angle from the axis of a spot light to where it is dark, radians
+    pub outer_cone: f32,
+    /// Directional and spot lights cast shadows if set
+    pub shadow: Option<ShadowSettings>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowSettings {
+    /// Width and height of the shadow map in texels
+    pub resolution: u32,
+    /// Depth offset against shadow acne, in depth buffer units
+    pub bias: f32,
+    /// Texels around the lookup averaged for soft edges, 0 for hard shadows
+    pub pcf_radius: u32,
+    /// Half size of the area a directional light covers around its position,
+    /// also how far spot lights without range cast shadows
+    pub extent: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            bias: 0.002,
+            pcf_radius: 1,
+            extent: 10.0,
+        }
+    }
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            kind: LightKind::Point,
+            position: cgmath::Point3::new(0.0, 0.0, 0.0),
+            direction: cgmath::Vector3::new(0.0, 0.0, -1.0),
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+            range: None,
+            inner_cone: 0.0,
+            outer_cone: std::f32::consts::FRAC_PI_4,
+            shadow: None,
+        }
+    }
+}
+
+impl Light {
+    /// `direction` normalized, the default one for a direction without length.
+    pub(crate) fn axis(&self) -> cgmath::Vector3<f32> {
+        let direction = self.direction.normalize();
+        if direction.x.is_finite() {
+            direction
+        } else {
+            Light::default().direction
+        }
+    }
+
+    /// Transform from world space into the shadow map, for lights casting shadows.
+    ///
+    /// `None` as well for shadows that would cover nothing, a cone without angle or no range.
+    pub(crate) fn shadow_view_proj(&self) -> Option<cgmath::Matrix4<f32>> {
+        let settings = self.shadow?;
+        let direction = self.axis();
+        // Any vector not parallel to the direction does
+        let up = if direction.y.abs() > 0.99 {
+            cgmath::Vector3::unit_x()
+        } else {
+            cgmath::Vector3::unit_y()
+        };
+
+        let (view, proj) = match self.kind {
+            LightKind::Point => return None,
+            LightKind::Directional => {
+                let extent = settings.extent;
+                if extent <= 0.0 {
+                    return None;
+                }
+                (
+                    cgmath::Matrix4::look_at_rh(
+                        self.position - direction * extent,
+                        self.position,
+                        up,
+                    ),
+                    cgmath::ortho(-extent, extent, -extent, extent, 0.0, 2.0 * extent),
+                )
+            }
+            LightKind::Spot => {
+                let fovy = (2.0 * self.outer_cone).min(3.0);
+                let far = self.range.unwrap_or(settings.extent);
+                if fovy <= 0.0 || far <= SPOT_SHADOW_NEAR {
+                    return None;
+                }
+                (
+                    cgmath::Matrix4::look_at_rh(self.position, self.position + direction, up),
+                    cgmath::perspective(cgmath::Rad(fovy), 1.0, SPOT_SHADOW_NEAR, far),
+                )
+            }
+        };
+
+        Some(camera::OPENGL_TO_WGPU_MATRIX * proj * view)
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct LightUniform {
     pub(crate) position: [f32; 3],
-    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    pub(crate) _padding: u32,
+    /// 0 point, 1 directional, 2 spot
+    pub(crate) kind: u32,
+    pub(crate) direction: [f32; 3],
+    /// 0 for lights without range
+    pub(crate) range: f32,
     pub(crate) color: [f32; 3],
+    pub(crate) intensity: f32,
+    pub(crate) inner_cone_cos: f32,
+    pub(crate) outer_cone_cos: f32,
     // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
-    pub(crate) _padding2: u32,
+    pub(crate) _padding: [u32; 2],
+    pub(crate) shadow_matrix: [[f32; 4]; 4],
+    /// Layer of the shadow maps, -1 for lights without shadow
+    pub(crate) shadow_layer: i32,
+    pub(crate) shadow_bias: f32,
+    /// Part of the layer the light renders into, its resolution over the size of the layer
+    pub(crate) shadow_scale: f32,
+    pub(crate) shadow_pcf_radius: i32,
+}
+
+impl From<&Light> for LightUniform {
+    fn from(light: &Light) -> Self {
+        Self {
+            position: light.position.into(),
+            kind: match light.kind {
+                LightKind::Point => 0,
+                LightKind::Directional => 1,
+                LightKind::Spot => 2,
+            },
+            direction: light.axis().into(),
+            range: light.range.unwrap_or(0.0),
+            color: light.color,
+            intensity: light.intensity,
+            inner_cone_cos: light.inner_cone.cos(),
+            outer_cone_cos: light.outer_cone.cos(),
+            _padding: [0; 2],
+            shadow_matrix: cgmath::Matrix4::identity().into(),
+            shadow_layer: -1,
+            shadow_bias: 0.0,
+            shadow_scale: 0.0,
+            shadow_pcf_radius: 0,
+        }
+    }
+}
+
+/// All lights of the state, bound at group 2 of the model pipelines
+/// and at group 1 of the gizmo pipeline.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightsUniform {
+    pub(crate) lights: [LightUniform; MAX_LIGHTS],
+    pub(crate) count: u32,
+    /// Scale of the image based lighting, 0 without an environment
+    pub(crate) environment_intensity: f32,
+    /// Mips of the prefiltered environment
+    pub(crate) environment_mip_count: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    pub(crate) _padding: u32,
+}
+
+impl LightsUniform {
+    /// `shadow_size` is the size of a layer of the shadow maps.
+    pub(crate) fn new(lights: &[Light], shadow_size: u32) -> Self {
+        let mut uniform = Self::zeroed();
+        for (u, light) in uniform.lights.iter_mut().zip(lights) {
+            *u = light.into();
+        }
+        uniform.count = lights.len().min(MAX_LIGHTS) as u32;
+
+        for caster in shadow::casters(lights) {
+            let u = &mut uniform.lights[caster.light];
+            u.shadow_matrix = caster.view_proj.into();
+            u.shadow_layer = caster.layer as i32;
+            u.shadow_bias = caster.settings.bias;
+            u.shadow_scale =
+                caster.settings.resolution.min(shadow_size) as f32 / shadow_size as f32;
+            u.shadow_pcf_radius = caster.settings.pcf_radius as i32;
+        }
+
+        uniform
+    }
 }
@@ -1,6 +1,15 @@
+pub(super) mod animation;
+pub(super) mod background;
+pub(super) mod bounds;
 pub(super) mod camera;
+pub(super) mod environment;
 pub(super) mod texture;
 pub(super) mod model;
 pub(super) mod light;
+pub(super) mod pipelines;
+pub(super) mod post;
+pub(super) mod scene;
+mod scene_file;
 pub(super) mod shader;
+pub(super) mod shadow;
 pub(super) mod instance;
\ No newline at end of file
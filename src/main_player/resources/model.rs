@@ -1,30 +1,88 @@
+use std::cell::RefCell;
+
 use crate::{main_player::error::PlayerErrorResult, requests};
 
-use self::{material::Material, mesh::Mesh};
+use self::{
+    material::{Material, PhongParams},
+    mesh::Mesh,
+    skeleton::{NodeTransform, Skeleton, Skin},
+};
+use super::{
+    animation::AnimationClip,
+    bounds::{Aabb, BoundingSphere},
+};
 
 pub mod draw_trait;
+mod gltf_loader;
 mod material;
 pub(super) mod mesh;
+pub mod skeleton;
 pub mod vertex;
 
+pub(crate) use material::MaterialKind;
+
 #[derive(Debug)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub(crate) materials: Vec<Material>,
+
+    pub skeleton: Skeleton,
+    /// Current local transform of every skeleton node, written by animations
+    pub(crate) pose: RefCell<Vec<NodeTransform>>,
+    pub(crate) skins: Vec<Skin>,
+    /// Bound by meshes without skin
+    pub(crate) rigid_skin: Skin,
+    pub animations: Vec<AnimationClip>,
+    /// Around all meshes in model space, skinned ones in their bind pose
+    pub aabb: Aabb,
+    /// `None` without vertices
+    pub bounding_sphere: Option<BoundingSphere>,
 }
 
-use std::io::{BufReader, Cursor};
+/// Layouts of the bind groups models create for their meshes.
+#[derive(Debug)]
+pub struct ModelLayouts {
+    pub material: wgpu::BindGroupLayout,
+    pub pbr_material: wgpu::BindGroupLayout,
+    pub skin: wgpu::BindGroupLayout,
+}
+
+impl ModelLayouts {
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            material: Material::create_bind_group_layout(device),
+            pbr_material: Material::create_pbr_bind_group_layout(device),
+            skin: Skin::create_bind_group_layout(device),
+        }
+    }
+}
+
+use std::{
+    io::{BufReader, Cursor},
+    path::Path,
+};
 
 impl Model {
+    /// Load a model from `static/obj` or, for `.gltf` and `.glb` files, from `static/gltf`.
     pub async fn from_file_name(
         name: &str,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        layout: &wgpu::BindGroupLayout,
+        layouts: &ModelLayouts,
+    ) -> PlayerErrorResult<Self> {
+        match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some("gltf") | Some("glb") => Self::from_gltf(name, device, queue, layouts).await,
+            _ => Self::from_obj(name, device, queue, layouts).await,
+        }
+    }
+
+    async fn from_obj(
+        name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &ModelLayouts,
     ) -> PlayerErrorResult<Self> {
-        let obj_text = requests::request_string(&format!("/static/obj/{}", name))
-        .await
-        .unwrap();
+        let obj_text = requests::request_string(&format!("/static/obj/{}", name)).await?;
 
         let obj_cursor = Cursor::new(obj_text);
         let mut obj_reader = BufReader::new(obj_cursor);
@@ -39,7 +97,7 @@ impl Model {
             |p| async move {
                 let mat_text = requests::request_string(&format!("/static/mtl/{}", p))
                     .await
-                    .unwrap();
+                    .map_err(|_| tobj::LoadError::OpenFileFailed)?;
                 tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
             },
         )
@@ -47,21 +105,83 @@ impl Model {
 
         let mut materials = Vec::new();
         for m in obj_materials? {
-            materials.push(Material::from_tobj_materials(
+            // Colors only, like the materials of CAD tools
+            let diffuse = match m.diffuse_texture.as_str() {
+                "" => requests::Image::from_color([255, 255, 255, 255]),
+                file => requests::Image::from_name(file).await?,
+            };
+            let normal = match m.normal_texture.as_str() {
+                "" => requests::Image::from_color(material::FLAT_NORMAL),
+                file => requests::Image::from_name(file).await?,
+            };
+
+            materials.push(Material::from_images(
                 &m.name,
-                requests::Image::from_name(&m.diffuse_texture).await?,
-                requests::Image::from_name(&m.normal_texture).await?,
+                diffuse,
+                normal,
+                PhongParams::from(&m),
                 device,
                 queue,
-                layout,
+                &layouts.material,
             )?)
         }
+        // Meshes without material use the first one
+        if materials.is_empty() {
+            materials.push(Material::from_images(
+                "default",
+                requests::Image::from_color([255, 255, 255, 255]),
+                requests::Image::from_color(material::FLAT_NORMAL),
+                PhongParams::default(),
+                device,
+                queue,
+                &layouts.material,
+            )?);
+        }
 
         let meshes = models
             .into_iter()
             .map(|m| Mesh::from_tobj_model(&m.name, &m, device))
             .collect::<Vec<_>>();
+        let (aabb, bounding_sphere) = Self::bounds(&meshes);
+
+        Ok(Self {
+            meshes,
+            materials,
+            skeleton: Skeleton::default(),
+            pose: RefCell::new(Vec::new()),
+            skins: Vec::new(),
+            rigid_skin: Skin::rigid(device, &layouts.skin),
+            animations: Vec::new(),
+            aabb,
+            bounding_sphere,
+        })
+    }
+
+    /// Bounds around those of `meshes`.
+    fn bounds(meshes: &[Mesh]) -> (Aabb, Option<BoundingSphere>) {
+        let aabb = meshes.iter().fold(Aabb::EMPTY, |aabb, m| aabb.union(m.aabb));
+        let bounding_sphere = meshes
+            .iter()
+            .filter_map(|m| m.bounding_sphere)
+            .reduce(BoundingSphere::union);
+        (aabb, bounding_sphere)
+    }
+
+    /// Upload the joint matrices for the current pose.
+    pub fn update_skins(&self, queue: &wgpu::Queue) {
+        if self.skins.is_empty() {
+            return;
+        }
+
+        let world = self.skeleton.world_matrices(&self.pose.borrow());
+        for skin in &self.skins {
+            skin.update(queue, &world);
+        }
+    }
 
-        Ok(Self { meshes, materials })
+    pub(crate) fn skin_of(&self, mesh: &Mesh) -> &Skin {
+        mesh.skin
+            .and_then(|i| self.skins.get(i))
+            .unwrap_or(&self.rigid_skin)
     }
 }
@@ -1,45 +1,39 @@
 use std::ops::Range;
 
-use super::{material::Material, mesh::Mesh, Model};
+use super::{
+    material::{Material, MaterialKind},
+    mesh::Mesh,
+    skeleton::Skin,
+    Model,
+};
 
 pub(crate) trait DrawModel<'a> {
-    fn draw_mesh(
-        &mut self,
-        mesh: &'a Mesh,
-        material: &'a Material,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'a Mesh,
         material: &'a Material,
+        skin: &'a Skin,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
 
-    fn draw_model(
-        &mut self,
-        model: &'a Model,
-        camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
-    );
-    fn draw_model_instanced(
+    /// Only the opaque meshes whose material is of `kind`, the bound pipeline has to match it.
+    /// Meshes whose material has `blend` are left to the transparent pass.
+    fn draw_model_instanced_of_kind(
         &mut self,
         model: &'a Model,
+        kind: MaterialKind,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
-    fn draw_model_instanced_with_material(
+    /// Positions only, for depth passes binding the camera at group 0 and the skin at group 1.
+    fn draw_model_shadow_instanced(
         &mut self,
         model: &'a Model,
-        material: &'a Material,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
-        light_bind_group: &'a wgpu::BindGroup,
     );
 }
 
@@ -47,20 +41,11 @@ impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a,
 {
-    fn draw_mesh(
-        &mut self,
-        mesh: &'b Mesh,
-        material: &'b Material,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_mesh_instanced(mesh, material, 0..1, camera_bind_group, light_bind_group);
-    }
-
     fn draw_mesh_instanced(
         &mut self,
         mesh: &'b Mesh,
         material: &'b Material,
+        skin: &'b Skin,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
@@ -70,53 +55,46 @@ where
         self.set_bind_group(0, &material.bind_group, &[]);
         self.set_bind_group(1, camera_bind_group, &[]);
         self.set_bind_group(2, light_bind_group, &[]);
+        self.set_bind_group(3, &skin.bind_group, &[]);
         self.draw_indexed(0..mesh.num_elements, 0, instances);
     }
 
-    fn draw_model(
-        &mut self,
-        model: &'b Model,
-        camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
-    ) {
-        self.draw_model_instanced(model, 0..1, camera_bind_group, light_bind_group);
-    }
-
-    fn draw_model_instanced(
+    fn draw_model_instanced_of_kind(
         &mut self,
         model: &'b Model,
+        kind: MaterialKind,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
         light_bind_group: &'b wgpu::BindGroup,
     ) {
         for mesh in &model.meshes {
             let material = &model.materials[mesh.material];
+            if material.kind != kind || material.blend {
+                continue;
+            }
             self.draw_mesh_instanced(
                 mesh,
                 material,
+                model.skin_of(mesh),
                 instances.clone(),
                 camera_bind_group,
                 light_bind_group,
             );
         }
     }
-    
-    fn draw_model_instanced_with_material(
+
+    fn draw_model_shadow_instanced(
         &mut self,
         model: &'b Model,
-        material: &'b Material,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
-        light_bind_group: &'b wgpu::BindGroup,
     ) {
         for mesh in &model.meshes {
-            self.draw_mesh_instanced(
-                mesh,
-                material,
-                instances.clone(),
-                camera_bind_group,
-                light_bind_group,
-            );
+            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            self.set_bind_group(0, camera_bind_group, &[]);
+            self.set_bind_group(1, &model.skin_of(mesh).bind_group, &[]);
+            self.draw_indexed(0..mesh.num_elements, 0, instances.clone());
         }
     }
 }
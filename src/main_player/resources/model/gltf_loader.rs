@@ -0,0 +1,627 @@
+use std::{cell::RefCell, collections::HashSet};
+
+use cgmath::{Matrix, SquareMatrix, Transform};
+
+use crate::{
+    main_player::error::{MainPlayerError, PlayerErrorResult},
+    requests,
+};
+
+use super::{
+    super::animation::{AnimationClip, Easing, Interpolation, Keyframes, NodeTrack, Track},
+    material::{Material, PbrFactors, PbrImages},
+    mesh::{smooth_normals, Mesh},
+    skeleton::{NodeTransform, Skeleton, Skin},
+    vertex::ModelVertex,
+    Model, ModelLayouts,
+};
+
+const GLTF_DIR: &str = "/static/gltf";
+
+impl Model {
+    /// Load a glTF 2.0 model, both `.gltf` with external or data uri buffers
+    /// and `.glb` with an embedded one.
+    ///
+    /// Node transforms of the scene are baked into the vertices of rigid meshes nothing animates,
+    /// skinned meshes are posed by their joints and meshes under animated nodes by their node instead.
+    /// Every primitive of every node becomes one [`Mesh`].
+    pub(super) async fn from_gltf(
+        name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layouts: &ModelLayouts,
+    ) -> PlayerErrorResult<Self> {
+        let path = format!("{}/{}", GLTF_DIR, name);
+        // External files are relative to the gltf file
+        let base_dir = &path[..path.rfind('/').unwrap_or(0)];
+
+        let gltf = gltf::Gltf::from_slice(&requests::request_binary(&path).await?)?;
+
+        let mut buffers = Vec::new();
+        for buffer in gltf.buffers() {
+            buffers.push(match buffer.source() {
+                gltf::buffer::Source::Bin => gltf
+                    .blob
+                    .clone()
+                    .ok_or(MainPlayerError::GltfBlobMissingError)?,
+                gltf::buffer::Source::Uri(uri) => load_uri(base_dir, uri).await?,
+            });
+        }
+
+        let mut images = Vec::new();
+        for image in gltf.images() {
+            let bytes = match image.source() {
+                gltf::image::Source::View { view, .. } => {
+                    let buffer = &buffers[view.buffer().index()];
+                    buffer
+                        .get(view.offset()..view.offset() + view.length())
+                        .ok_or(MainPlayerError::GltfBufferRangeError)?
+                        .to_vec()
+                }
+                gltf::image::Source::Uri { uri, .. } => load_uri(base_dir, uri).await?,
+            };
+            images.push(requests::Image::from_bytes(&bytes)?);
+        }
+
+        let mut materials = gltf
+            .materials()
+            .map(|m| load_material(&m, &images, device, queue, &layouts.pbr_material))
+            .collect::<PlayerErrorResult<Vec<_>>>()?;
+
+        // Primitives without material use the default material of glTF
+        let default_material = materials.len();
+        materials.push(Material::pbr(
+            "default",
+            PbrImages::default(),
+            PbrFactors::default(),
+            false,
+            device,
+            queue,
+            &layouts.pbr_material,
+        )?);
+
+        // Nodes are loaded recursively, a cycle would never end
+        check_hierarchy(
+            &gltf
+                .nodes()
+                .map(|n| n.children().map(|c| c.index()).collect())
+                .collect::<Vec<_>>(),
+        )?;
+
+        let roots = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            Some(scene) => scene.nodes().collect::<Vec<_>>(),
+            // Without scenes every node nobody refers to as child is a root
+            None => {
+                let children = gltf
+                    .nodes()
+                    .flat_map(|n| n.children().map(|c| c.index()))
+                    .collect::<HashSet<_>>();
+                gltf.nodes()
+                    .filter(|n| !children.contains(&n.index()))
+                    .collect()
+            }
+        };
+
+        let mut skins = gltf
+            .skins()
+            .map(|s| load_skin(&s, &buffers, device, &layouts.skin))
+            .collect::<PlayerErrorResult<Vec<_>>>()?;
+
+        let context = NodeContext {
+            buffers: &buffers,
+            default_material,
+            animated: gltf
+                .animations()
+                .flat_map(|a| {
+                    a.channels()
+                        .map(|c| c.target().node().index())
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+            device,
+            skin_layout: &layouts.skin,
+        };
+        let mut meshes = Vec::new();
+        for node in roots {
+            load_node(
+                &node,
+                cgmath::Matrix4::identity(),
+                false,
+                &context,
+                &mut meshes,
+                &mut skins,
+            )?;
+        }
+
+        let animations = gltf
+            .animations()
+            .map(|a| load_animation(name, &a, &buffers))
+            .collect();
+
+        let skeleton = load_skeleton(&gltf);
+        let (aabb, bounding_sphere) = Self::bounds(&meshes);
+        let model = Self {
+            meshes,
+            materials,
+            pose: RefCell::new(skeleton.rest_pose()),
+            skeleton,
+            skins,
+            rigid_skin: Skin::rigid(device, &layouts.skin),
+            animations,
+            aabb,
+            bounding_sphere,
+        };
+        // Skinned and animated meshes have no baked transform, pose them at least once
+        model.update_skins(queue);
+
+        Ok(model)
+    }
+}
+
+/// Bytes behind a uri of a glTF file, either decoded from a base64 data uri or requested.
+async fn load_uri(base_dir: &str, uri: &str) -> PlayerErrorResult<Vec<u8>> {
+    match uri.strip_prefix("data:") {
+        Some(data) => {
+            let (_, encoded) = data
+                .split_once(";base64,")
+                .ok_or(MainPlayerError::UnsupportedUriError)?;
+
+            Ok(base64::decode(encoded)?)
+        }
+        None => Ok(requests::request_binary(&format!("{}/{}", base_dir, uri)).await?),
+    }
+}
+
+fn load_material(
+    material: &gltf::Material,
+    images: &[requests::Image],
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> PlayerErrorResult<Material> {
+    let pbr = material.pbr_metallic_roughness();
+    let image = |texture: gltf::Texture| images[texture.source().index()].clone();
+
+    let defaults = PbrImages::default();
+    let images = PbrImages {
+        base_color: pbr
+            .base_color_texture()
+            .map_or(defaults.base_color, |info| image(info.texture())),
+        metallic_roughness: pbr
+            .metallic_roughness_texture()
+            .map_or(defaults.metallic_roughness, |info| image(info.texture())),
+        normal: material
+            .normal_texture()
+            .map_or(defaults.normal, |normal| image(normal.texture())),
+        occlusion: material
+            .occlusion_texture()
+            .map_or(defaults.occlusion, |occlusion| image(occlusion.texture())),
+        emissive: material
+            .emissive_texture()
+            .map_or(defaults.emissive, |info| image(info.texture())),
+    };
+
+    let factors = PbrFactors {
+        base_color: pbr.base_color_factor(),
+        emissive: material.emissive_factor(),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        normal_scale: material.normal_texture().map_or(1.0, |n| n.scale()),
+        occlusion_strength: material.occlusion_texture().map_or(1.0, |o| o.strength()),
+        ..Default::default()
+    };
+
+    Material::pbr(
+        material.name().unwrap_or("gltf material"),
+        images,
+        factors,
+        material.alpha_mode() == gltf::material::AlphaMode::Blend,
+        device,
+        queue,
+        layout,
+    )
+}
+
+/// What the nodes of a glTF file load their meshes with.
+struct NodeContext<'a> {
+    buffers: &'a [Vec<u8>],
+    default_material: usize,
+    /// Nodes the animations move, meshes under them are posed instead of baked
+    animated: HashSet<usize>,
+    device: &'a wgpu::Device,
+    skin_layout: &'a wgpu::BindGroupLayout,
+}
+
+/// What places the vertices of a primitive.
+#[derive(Debug, Clone, Copy)]
+enum Placement {
+    /// The transform of the node, baked into them
+    Baked(cgmath::Matrix4<f32>),
+    /// The joints of the skin at the index
+    Skinned(usize),
+    /// The node, through the skin at the index with it as the only joint
+    Node(usize),
+}
+
+/// Fails unless every node has at most one parent and none is its own ancestor,
+/// `children` holds the child indices of every node.
+fn check_hierarchy(children: &[Vec<usize>]) -> PlayerErrorResult<()> {
+    let mut parents = vec![None; children.len()];
+    for (parent, children) in children.iter().enumerate() {
+        for &child in children {
+            if parents[child].replace(parent).is_some() {
+                return Err(MainPlayerError::GltfHierarchyError(format!(
+                    "node {} has more than one parent",
+                    child
+                )));
+            }
+        }
+    }
+
+    // With one parent each, an ancestor chain longer than the node count runs in a cycle
+    for node in 0..parents.len() {
+        let mut ancestor = parents[node];
+        for _ in 0..parents.len() {
+            ancestor = match ancestor {
+                Some(a) => parents[a],
+                None => break,
+            };
+        }
+        if ancestor.is_some() {
+            return Err(MainPlayerError::GltfHierarchyError(format!(
+                "node {} is its own ancestor",
+                node
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn load_node(
+    node: &gltf::Node,
+    parent_transform: cgmath::Matrix4<f32>,
+    parent_animated: bool,
+    context: &NodeContext,
+    meshes: &mut Vec<Mesh>,
+    skins: &mut Vec<Skin>,
+) -> PlayerErrorResult<()> {
+    let transform = parent_transform * cgmath::Matrix4::from(node.transform().matrix());
+    let animated = parent_animated || context.animated.contains(&node.index());
+
+    if let Some(mesh) = node.mesh() {
+        let name = node.name().or_else(|| mesh.name()).unwrap_or("gltf mesh");
+        let placement = match node.skin() {
+            // The joints place skinned meshes, the transform of their node is ignored
+            Some(skin) => Placement::Skinned(skin.index()),
+            None if animated => {
+                skins.push(Skin::new(
+                    name,
+                    vec![node.index()],
+                    vec![cgmath::Matrix4::identity()],
+                    context.device,
+                    context.skin_layout,
+                )?);
+                Placement::Node(skins.len() - 1)
+            }
+            None => Placement::Baked(transform),
+        };
+
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                continue;
+            }
+
+            if let Some(mut m) = load_primitive(name, &primitive, placement, context)? {
+                // Bounds in model space like those of baked meshes, in the rest pose
+                if let Placement::Node(_) = placement {
+                    m.aabb = m.aabb.transformed(&transform);
+                    m.bounding_sphere = m.bounding_sphere.map(|s| s.transformed(&transform));
+                }
+                meshes.push(m);
+            }
+        }
+    }
+
+    for child in node.children() {
+        load_node(&child, transform, animated, context, meshes, skins)?;
+    }
+
+    Ok(())
+}
+
+fn load_primitive(
+    name: &str,
+    primitive: &gltf::Primitive,
+    placement: Placement,
+    context: &NodeContext,
+) -> PlayerErrorResult<Option<Mesh>> {
+    let buffers = context.buffers;
+    let reader = primitive.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+    let (transform, skin) = match placement {
+        Placement::Baked(transform) => (transform, None),
+        Placement::Skinned(skin) | Placement::Node(skin) => {
+            (cgmath::Matrix4::identity(), Some(skin))
+        }
+    };
+
+    let positions = match reader.read_positions() {
+        Some(positions) => positions.collect::<Vec<_>>(),
+        None => return Ok(None),
+    };
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|t| t.into_f32().collect::<Vec<_>>())
+        .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+
+    let mut indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let normals = reader.read_normals().map(|n| n.collect::<Vec<_>>());
+
+    let (joints, weights) = match placement {
+        // All on the only joint
+        Placement::Node(_) => (
+            vec![[0; 4]; positions.len()],
+            vec![[1.0, 0.0, 0.0, 0.0]; positions.len()],
+        ),
+        _ => (
+            reader
+                .read_joints(0)
+                .map(|j| j.into_u16().map(|j| j.map(u32::from)).collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0; 4]; positions.len()]),
+            reader
+                .read_weights(0)
+                .map(|w| w.into_f32().collect::<Vec<_>>())
+                .unwrap_or_else(|| vec![[0.0; 4]; positions.len()]),
+        ),
+    };
+
+    check_primitive(
+        name,
+        positions.len(),
+        &[
+            ("TEXCOORD_0", tex_coords.len()),
+            ("NORMAL", normals.as_ref().map_or(positions.len(), Vec::len)),
+            ("JOINTS_0", joints.len()),
+            ("WEIGHTS_0", weights.len()),
+        ],
+        &indices,
+    )?;
+    let normals = normals.unwrap_or_else(|| smooth_normals(&positions, &indices));
+
+    let normal_matrix = {
+        let m = transform;
+        cgmath::Matrix3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate())
+            .invert()
+            .unwrap_or_else(cgmath::Matrix3::identity)
+            .transpose()
+    };
+
+    let vertices = positions
+        .iter()
+        .zip(tex_coords)
+        .zip(normals)
+        .zip(joints.into_iter().zip(weights))
+        .map(
+            |(((position, tex_coords), normal), (joints, weights))| ModelVertex {
+                position: transform
+                    .transform_point(cgmath::Point3::from(*position))
+                    .into(),
+                tex_coords,
+                normal: (normal_matrix * cgmath::Vector3::from(normal)).into(),
+                tangent: [0.0; 3],
+                bitangent: [0.0; 3],
+                joints,
+                weights,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    // A mirroring transform turns the triangles inside out
+    if transform.determinant() < 0.0 {
+        indices.chunks_mut(3).for_each(|c| c.reverse());
+    }
+
+    Ok(Some(Mesh::new(
+        name,
+        vertices,
+        &indices,
+        primitive
+            .material()
+            .index()
+            .unwrap_or(context.default_material),
+        skin,
+        context.device,
+    )))
+}
+
+/// Accessors of a primitive hold one value per position and its indices whole triangles of them,
+/// `attributes` are names and lengths.
+fn check_primitive(
+    name: &str,
+    positions: usize,
+    attributes: &[(&str, usize)],
+    indices: &[u32],
+) -> PlayerErrorResult<()> {
+    let invalid = |problem: String| {
+        Err(MainPlayerError::GltfPrimitiveError(
+            name.to_owned(),
+            problem,
+        ))
+    };
+
+    if let Some((attribute, len)) = attributes.iter().find(|(_, len)| *len != positions) {
+        return invalid(format!(
+            "{} has {} values for {} positions",
+            attribute, len, positions
+        ));
+    }
+    if !indices.chunks_exact(3).remainder().is_empty() {
+        return invalid(format!("{} indices are no whole triangles", indices.len()));
+    }
+    if let Some(index) = indices.iter().find(|&&i| i as usize >= positions) {
+        return invalid(format!(
+            "index {} is past its {} vertices",
+            index, positions
+        ));
+    }
+
+    Ok(())
+}
+
+/// Every node of the file is a node of the skeleton, indices are kept.
+fn load_skeleton(gltf: &gltf::Gltf) -> Skeleton {
+    let mut parents = vec![None; gltf.nodes().len()];
+    for node in gltf.nodes() {
+        for child in node.children() {
+            parents[child.index()] = Some(node.index());
+        }
+    }
+
+    let rest_pose = gltf
+        .nodes()
+        .map(|node| {
+            let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+            NodeTransform {
+                translation: translation.into(),
+                rotation: cgmath::Quaternion::new(w, x, y, z),
+                scale: scale.into(),
+            }
+        })
+        .collect();
+
+    Skeleton::new(parents, rest_pose)
+}
+
+fn load_skin(
+    skin: &gltf::Skin,
+    buffers: &[Vec<u8>],
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+) -> PlayerErrorResult<Skin> {
+    let name = skin.name().unwrap_or("gltf skin");
+    let joints = skin.joints().map(|j| j.index()).collect::<Vec<_>>();
+
+    let reader = skin.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+    let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+        Some(matrices) => matrices.map(cgmath::Matrix4::from).collect(),
+        None => vec![cgmath::Matrix4::identity(); joints.len()],
+    };
+
+    Skin::new(name, joints, inverse_bind_matrices, device, layout)
+}
+
+/// The tracks target the model `model`, morph target weights are not supported
+/// and their channels are skipped.
+fn load_animation(model: &str, animation: &gltf::Animation, buffers: &[Vec<u8>]) -> AnimationClip {
+    use gltf::animation::util::ReadOutputs;
+
+    let tracks = animation
+        .channels()
+        .filter_map(|channel| {
+            let reader = channel.reader(|buffer| Some(buffers[buffer.index()].as_slice()));
+
+            let times = reader.read_inputs()?.collect::<Vec<_>>();
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+            let vectors = |values: Vec<[f32; 3]>| Keyframes {
+                times: times.clone(),
+                values: values.into_iter().map(cgmath::Vector3::from).collect(),
+                interpolation,
+                easing: Easing::Linear,
+            };
+
+            let track = match reader.read_outputs()? {
+                ReadOutputs::Translations(t) => NodeTrack::Translation(vectors(t.collect())),
+                ReadOutputs::Scales(s) => NodeTrack::Scale(vectors(s.collect())),
+                ReadOutputs::Rotations(r) => NodeTrack::Rotation(Keyframes {
+                    times: times.clone(),
+                    values: r
+                        .into_f32()
+                        .map(|[x, y, z, w]| cgmath::Quaternion::new(w, x, y, z))
+                        .collect(),
+                    interpolation,
+                    easing: Easing::Linear,
+                }),
+                ReadOutputs::MorphTargetWeights(_) => return None,
+            };
+
+            Some(Track::Node {
+                model: model.to_string(),
+                node: channel.target().node().index(),
+                track,
+            })
+        })
+        .collect();
+
+    AnimationClip::new(animation.name().unwrap_or("gltf animation"), tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy_of_trees() {
+        assert!(check_hierarchy(&[vec![1, 2], vec![3], vec![], vec![], vec![]]).is_ok());
+        assert!(check_hierarchy(&[]).is_ok());
+    }
+
+    #[test]
+    fn hierarchy_with_cycles() {
+        for children in [
+            vec![vec![1], vec![0]],
+            vec![vec![0]],
+            vec![vec![1], vec![2], vec![1]],
+        ] {
+            assert!(
+                matches!(
+                    check_hierarchy(&children),
+                    Err(MainPlayerError::GltfHierarchyError(_))
+                ),
+                "{:?}",
+                children
+            );
+        }
+    }
+
+    #[test]
+    fn primitive_with_matching_accessors() {
+        let attributes = [("NORMAL", 4), ("TEXCOORD_0", 4)];
+        assert!(check_primitive("quad", 4, &attributes, &[0, 1, 2, 2, 3, 0]).is_ok());
+        assert!(check_primitive("empty", 0, &[], &[]).is_ok());
+    }
+
+    #[test]
+    fn primitive_accessors_of_other_lengths() {
+        let attributes = [("NORMAL", 3), ("TEXCOORD_0", 2)];
+        let error = check_primitive("quad", 3, &attributes, &[0, 1, 2]).unwrap_err();
+        assert!(
+            error.to_string().contains("TEXCOORD_0 has 2 values"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn primitive_indices_past_the_vertices() {
+        assert!(matches!(
+            check_primitive("triangle", 3, &[], &[0, 1, 3]),
+            Err(MainPlayerError::GltfPrimitiveError(..))
+        ));
+    }
+
+    #[test]
+    fn primitive_with_a_partial_triangle() {
+        assert!(matches!(
+            check_primitive("triangle", 3, &[], &[0, 1, 2, 0]),
+            Err(MainPlayerError::GltfPrimitiveError(..))
+        ));
+    }
+}
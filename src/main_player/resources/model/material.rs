@@ -1,26 +1,187 @@
+use wgpu::util::DeviceExt;
+
 use crate::{main_player::{error::PlayerErrorResult, resources::texture}, requests};
 
+/// Normal map pixel pointing straight out of the surface
+pub(super) const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+/// Shading model of a material, every kind has its own pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MaterialKind {
+    /// Blinn-Phong with a diffuse and a normal map, `bp.wgsl`
+    Phong,
+    /// Metallic-roughness, `pbr.wgsl`
+    Pbr,
+}
+
 #[derive(Debug)]
 pub(crate) struct Material {
     pub(crate) name: String,
-    pub(crate) diffuse_texture: texture::Texture,
-    pub normal_texture: texture::Texture,
+    pub(crate) kind: MaterialKind,
+    /// Drawn after the opaque meshes, blended over what is behind it
+    pub(crate) blend: bool,
+    /// Kept alive for the bind group
+    _textures: Vec<texture::Texture>,
     pub(crate) bind_group: wgpu::BindGroup,
 }
 
+/// Colors and factors of an MTL material, laid out for the uniform of `bp.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PhongParams {
+    /// `Ka`
+    pub(crate) ambient: [f32; 3],
+    /// `Ns`, the exponent of the highlight
+    pub(crate) shininess: f32,
+    /// `Kd`
+    pub(crate) diffuse: [f32; 3],
+    /// `d`, the opacity
+    pub(crate) dissolve: f32,
+    /// `Ks`
+    pub(crate) specular: [f32; 3],
+    /// `Ni`, the index of refraction
+    pub(crate) optical_density: f32,
+    /// `Ke`
+    pub(crate) emissive: [f32; 3],
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    pub(crate) _padding: u32,
+}
+
+impl Default for PhongParams {
+    /// Plain white without highlight.
+    fn default() -> Self {
+        Self {
+            ambient: [1.0; 3],
+            shininess: 1.0,
+            diffuse: [1.0; 3],
+            dissolve: 1.0,
+            specular: [0.0; 3],
+            optical_density: 1.0,
+            emissive: [0.0; 3],
+            _padding: 0,
+        }
+    }
+}
+
+impl From<&tobj::Material> for PhongParams {
+    fn from(m: &tobj::Material) -> Self {
+        // tobj knows no `Ke`
+        let emissive = m
+            .unknown_param
+            .get("Ke")
+            .and_then(|v| {
+                let v = v
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<f32>, _>>()
+                    .ok()?;
+                <[f32; 3]>::try_from(v.as_slice()).ok()
+            })
+            .unwrap_or([0.0; 3]);
+        // A missing `Kd` reads as black, files with only `map_Kd` mean the map as it is
+        let diffuse = if m.diffuse == [0.0; 3] && !m.diffuse_texture.is_empty() {
+            [1.0; 3]
+        } else {
+            m.diffuse
+        };
+        // Illumination models 0 and 1 have no highlight
+        let specular = match m.illumination_model {
+            Some(0) | Some(1) => [0.0; 3],
+            _ => m.specular,
+        };
+
+        Self {
+            ambient: m.ambient,
+            shininess: m.shininess.max(1.0),
+            diffuse,
+            dissolve: m.dissolve,
+            specular,
+            optical_density: m.optical_density,
+            emissive,
+            _padding: 0,
+        }
+    }
+}
+
+/// Maps of a metallic-roughness material, as in glTF.
+#[derive(Debug, Clone)]
+pub(crate) struct PbrImages {
+    pub(crate) base_color: requests::Image,
+    /// Roughness in green, metallic in blue
+    pub(crate) metallic_roughness: requests::Image,
+    pub(crate) normal: requests::Image,
+    /// Ambient occlusion in red
+    pub(crate) occlusion: requests::Image,
+    pub(crate) emissive: requests::Image,
+}
+
+impl Default for PbrImages {
+    /// Maps leaving the factors alone.
+    fn default() -> Self {
+        let white = requests::Image::from_color([255, 255, 255, 255]);
+
+        Self {
+            base_color: white.clone(),
+            metallic_roughness: white.clone(),
+            normal: requests::Image::from_color(FLAT_NORMAL),
+            occlusion: white.clone(),
+            emissive: white,
+        }
+    }
+}
+
+/// Factors multiplied with the maps of a metallic-roughness material,
+/// laid out for the uniform of `pbr.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct PbrFactors {
+    pub(crate) base_color: [f32; 4],
+    pub(crate) emissive: [f32; 3],
+    pub(crate) metallic: f32,
+    pub(crate) roughness: f32,
+    pub(crate) normal_scale: f32,
+    pub(crate) occlusion_strength: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    pub(crate) _padding: u32,
+}
+
+impl Default for PbrFactors {
+    /// The defaults of glTF.
+    fn default() -> Self {
+        Self {
+            base_color: [1.0; 4],
+            emissive: [0.0; 3],
+            metallic: 1.0,
+            roughness: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            _padding: 0,
+        }
+    }
+}
+
 impl Material {
-    pub(super) fn from_tobj_materials(
+    /// Blinn-Phong material.
+    pub(super) fn from_images(
         name: &str,
         texture_img: requests::Image,
         normal_img: requests::Image,
+        params: PhongParams,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         layout: &wgpu::BindGroupLayout,
     ) -> PlayerErrorResult<Self> {
+        let blend = params.dissolve < 1.0 || !texture_img.is_opaque();
         let diffuse_texture =
             texture::Texture::from_image(device, queue, texture_img, None, false)?;
         let normal_texture = texture::Texture::from_image(device, queue, normal_img, None, true)?;
 
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Params Buffer", name)),
+            contents: bytemuck::cast_slice(&[params]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout,
             entries: &[
@@ -40,15 +201,174 @@ impl Material {
                     binding: 3,
                     resource: wgpu::BindingResource::Sampler(&normal_texture.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params_buffer.as_entire_binding(),
+                },
             ],
             label: Some(name),
         });
 
         Ok(Self {
             name: name.to_string(),
-            diffuse_texture,
-            normal_texture,
+            kind: MaterialKind::Phong,
+            blend,
+            _textures: vec![diffuse_texture, normal_texture],
+            bind_group,
+        })
+    }
+
+    /// Metallic-roughness material, `blend` for the `BLEND` alpha mode of glTF.
+    pub(super) fn pbr(
+        name: &str,
+        images: PbrImages,
+        factors: PbrFactors,
+        blend: bool,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> PlayerErrorResult<Self> {
+        // Only colors are srgb, the other maps hold linear data like normal maps do
+        let textures = vec![
+            texture::Texture::from_image(device, queue, images.base_color, None, false)?,
+            texture::Texture::from_image(device, queue, images.metallic_roughness, None, true)?,
+            texture::Texture::from_image(device, queue, images.normal, None, true)?,
+            texture::Texture::from_image(device, queue, images.occlusion, None, true)?,
+            texture::Texture::from_image(device, queue, images.emissive, None, false)?,
+        ];
+
+        let factors_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Factors Buffer", name)),
+            contents: bytemuck::cast_slice(&[factors]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: factors_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&textures[0].sampler),
+            },
+        ];
+        entries.extend(
+            textures
+                .iter()
+                .enumerate()
+                .map(|(i, t)| wgpu::BindGroupEntry {
+                    binding: 2 + i as u32,
+                    resource: wgpu::BindingResource::TextureView(&t.view),
+                }),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &entries,
+            label: Some(name),
+        });
+
+        Ok(Self {
+            name: name.to_string(),
+            kind: MaterialKind::Pbr,
+            blend,
+            _textures: textures,
             bind_group,
         })
     }
+
+    pub(crate) fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("texture_bind_group_layout"),
+        })
+    }
+
+    /// Factors, one sampler shared by all maps, then base color, metallic-roughness,
+    /// normal, occlusion and emissive maps.
+    pub(crate) fn create_pbr_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(2),
+                texture_entry(3),
+                texture_entry(4),
+                texture_entry(5),
+                texture_entry(6),
+            ],
+            label: Some("pbr_bind_group_layout"),
+        })
+    }
 }
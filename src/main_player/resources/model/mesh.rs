@@ -1,6 +1,10 @@
+use cgmath::InnerSpace;
 use wgpu::util::DeviceExt;
 
-use super::vertex::ModelVertex;
+use super::{
+    super::bounds::{Aabb, BoundingSphere},
+    vertex::ModelVertex,
+};
 
 #[derive(Debug)]
 pub struct Mesh {
@@ -9,35 +13,82 @@ pub struct Mesh {
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    /// Index into the skins of the model, `None` for rigid meshes
+    pub skin: Option<usize>,
+    /// In model space, skinned meshes in their bind pose.
+    /// Transparent meshes are sorted by the distance of its center
+    pub aabb: Aabb,
+    /// `None` without vertices
+    pub bounding_sphere: Option<BoundingSphere>,
 }
 
 impl Mesh {
     pub(super) fn from_tobj_model(name: &str, model: &tobj::Model, device: &wgpu::Device) -> Self {
-        let mut vertices = (0..model.mesh.positions.len() / 3)
-            .map(|i| ModelVertex {
-                position: [
-                    model.mesh.positions[i * 3],
-                    model.mesh.positions[i * 3 + 1],
-                    model.mesh.positions[i * 3 + 2],
-                ],
-                tex_coords: [model.mesh.texcoords[i * 2], model.mesh.texcoords[i * 2 + 1]],
-                normal: [
-                    model.mesh.normals[i * 3],
-                    model.mesh.normals[i * 3 + 1],
-                    model.mesh.normals[i * 3 + 2],
-                ],
+        let positions = model
+            .mesh
+            .positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+        // Exports of CAD tools often have neither uv nor normals
+        let normals = match model.mesh.normals.len() {
+            0 => smooth_normals(&positions, &model.mesh.indices),
+            _ => model
+                .mesh
+                .normals
+                .chunks_exact(3)
+                .map(|n| [n[0], n[1], n[2]])
+                .collect(),
+        };
+        let tex_coords = model
+            .mesh
+            .texcoords
+            .chunks_exact(2)
+            .map(|t| [t[0], t[1]])
+            .chain(std::iter::repeat([0.0; 2]));
+
+        let vertices = positions
+            .into_iter()
+            .zip(normals)
+            .zip(tex_coords)
+            .map(|((position, normal), tex_coords)| ModelVertex {
+                position,
+                tex_coords,
+                normal,
                 tangent: [0.0; 3],
                 bitangent: [0.0; 3],
+                joints: [0; 4],
+                weights: [0.0; 4],
             })
             .collect::<Vec<_>>();
 
-        let indices = &model.mesh.indices;
+        Self::new(
+            name,
+            vertices,
+            &model.mesh.indices,
+            model.mesh.material_id.unwrap_or(0),
+            None,
+            device,
+        )
+    }
+
+    /// Upload a triangle list, tangents and bitangents of the vertices are calculated here.
+    ///
+    /// `indices` must hold whole triangles of `vertices`, the loaders check them.
+    pub(super) fn new(
+        name: &str,
+        mut vertices: Vec<ModelVertex>,
+        indices: &[u32],
+        material: usize,
+        skin: Option<usize>,
+        device: &wgpu::Device,
+    ) -> Self {
         let mut triangles_included = vec![0; vertices.len()];
 
         // Calculate tangents and bitangets. We're going to
         // use the triangles, so we need to loop through the
         // indices in chunks of 3
-        for c in indices.chunks(3) {
+        for c in indices.chunks_exact(3) {
             let v0 = vertices[c[0] as usize];
             let v1 = vertices[c[1] as usize];
             let v2 = vertices[c[2] as usize];
@@ -66,6 +117,10 @@ impl Mesh {
             // Luckily, the place I found this equation provided
             // the solution!
             let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+            // Triangles without uv mapping have no tangent space
+            if !r.is_finite() {
+                continue;
+            }
             let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
             // We flip the bitangent to enable right-handed normal
             // maps with wgpu texture coordinate system
@@ -93,12 +148,35 @@ impl Mesh {
 
         // Average the tangents/bitangents
         for (i, n) in triangles_included.into_iter().enumerate() {
+            let v = &mut vertices[i];
+
+            if n == 0 {
+                // Without uv any tangent space around the normal works for a flat normal map
+                let normal = cgmath::Vector3::from(v.normal);
+                let axis = if normal.x.abs() < 0.9 {
+                    cgmath::Vector3::unit_x()
+                } else {
+                    cgmath::Vector3::unit_y()
+                };
+                let tangent = normal.cross(axis).normalize();
+
+                v.tangent = tangent.into();
+                v.bitangent = normal.cross(tangent).into();
+                continue;
+            }
+
             let denom = 1.0 / n as f32;
-            let mut v = &mut vertices[i];
             v.tangent = (cgmath::Vector3::from(v.tangent) * denom).into();
             v.bitangent = (cgmath::Vector3::from(v.bitangent) * denom).into();
         }
 
+        let positions = vertices
+            .iter()
+            .map(|v| cgmath::Point3::from(v.position))
+            .collect::<Vec<_>>();
+        let aabb = Aabb::from_points(positions.iter().copied());
+        let bounding_sphere = BoundingSphere::from_points(&positions);
+
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{:?} Vertex Buffer", name)),
             contents: bytemuck::cast_slice(&vertices),
@@ -106,7 +184,7 @@ impl Mesh {
         });
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some(&format!("{:?} Index Buffer", name)),
-            contents: bytemuck::cast_slice(&model.mesh.indices),
+            contents: bytemuck::cast_slice(indices),
             usage: wgpu::BufferUsages::INDEX,
         });
 
@@ -114,8 +192,37 @@ impl Mesh {
             name: name.to_string(),
             vertex_buffer,
             index_buffer,
-            num_elements: model.mesh.indices.len() as u32,
-            material: model.mesh.material_id.unwrap_or(0),
+            num_elements: indices.len() as u32,
+            material,
+            skin,
+            aabb,
+            bounding_sphere,
         }
     }
 }
+
+/// Area weighted average of the faces around every vertex.
+pub(super) fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![cgmath::Vector3::new(0.0, 0.0, 0.0); positions.len()];
+
+    for c in indices.chunks_exact(3) {
+        let p0 = cgmath::Vector3::from(positions[c[0] as usize]);
+        let p1 = cgmath::Vector3::from(positions[c[1] as usize]);
+        let p2 = cgmath::Vector3::from(positions[c[2] as usize]);
+
+        // Not normalized, so bigger triangles weigh more
+        let face_normal = (p1 - p0).cross(p2 - p0);
+        c.iter().for_each(|&i| normals[i as usize] += face_normal);
+    }
+
+    normals
+        .into_iter()
+        .map(|n| {
+            if n.magnitude2() > 0.0 {
+                n.normalize().into()
+            } else {
+                [0.0, 1.0, 0.0]
+            }
+        })
+        .collect()
+}
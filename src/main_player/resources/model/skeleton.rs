@@ -0,0 +1,187 @@
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+use crate::main_player::error::{MainPlayerError, PlayerErrorResult};
+
+/// Joints a single skin can have, matches the array in the skinning shader.
+pub const MAX_JOINTS: usize = 128;
+
+/// Translation, rotation and scale of a node relative to its parent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NodeTransform {
+    pub translation: cgmath::Vector3<f32>,
+    pub rotation: cgmath::Quaternion<f32>,
+    pub scale: cgmath::Vector3<f32>,
+}
+
+impl Default for NodeTransform {
+    fn default() -> Self {
+        Self {
+            translation: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl NodeTransform {
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.translation)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Node hierarchy of a model, the joints of its skins are nodes of it.
+#[derive(Debug, Default)]
+pub struct Skeleton {
+    parents: Vec<Option<usize>>,
+    rest_pose: Vec<NodeTransform>,
+    /// Node indices, parents always come before their children
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    pub fn new(parents: Vec<Option<usize>>, rest_pose: Vec<NodeTransform>) -> Self {
+        let mut order = (0..parents.len())
+            .filter(|&i| parents[i].is_none())
+            .collect::<Vec<_>>();
+
+        let mut i = 0;
+        while i < order.len() {
+            let parent = order[i];
+            order.extend((0..parents.len()).filter(|&c| parents[c] == Some(parent)));
+            i += 1;
+        }
+
+        Self {
+            parents,
+            rest_pose,
+            order,
+        }
+    }
+
+    /// Local transform of every node when nothing is animated.
+    pub fn rest_pose(&self) -> Vec<NodeTransform> {
+        self.rest_pose.clone()
+    }
+
+    /// Transforms from node space to model space for every node of `pose`.
+    pub fn world_matrices(&self, pose: &[NodeTransform]) -> Vec<cgmath::Matrix4<f32>> {
+        let mut world = vec![cgmath::Matrix4::identity(); pose.len()];
+
+        for &node in &self.order {
+            let local = pose[node].matrix();
+            world[node] = match self.parents[node] {
+                Some(parent) => world[parent] * local,
+                None => local,
+            };
+        }
+
+        world
+    }
+}
+
+/// Joints deforming a skinned mesh, with the uniform buffer of their matrices.
+#[derive(Debug)]
+pub(crate) struct Skin {
+    /// Skeleton node of every joint
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<cgmath::Matrix4<f32>>,
+    buffer: wgpu::Buffer,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl Skin {
+    /// Fails with more than [`MAX_JOINTS`] joints, the shaders have no room for the others.
+    pub(crate) fn new(
+        name: &str,
+        joints: Vec<usize>,
+        inverse_bind_matrices: Vec<cgmath::Matrix4<f32>>,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> PlayerErrorResult<Self> {
+        if joints.len() > MAX_JOINTS {
+            return Err(MainPlayerError::TooManyJointsError(
+                name.to_owned(),
+                joints.len(),
+            ));
+        }
+
+        Ok(Self::create(
+            name,
+            joints,
+            inverse_bind_matrices,
+            device,
+            layout,
+        ))
+    }
+
+    /// Skin for meshes without joints, the shader leaves vertices without weights alone.
+    pub(crate) fn rigid(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
+        Self::create("rigid skin", Vec::new(), Vec::new(), device, layout)
+    }
+
+    fn create(
+        name: &str,
+        joints: Vec<usize>,
+        inverse_bind_matrices: Vec<cgmath::Matrix4<f32>>,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        let identities: Vec<[[f32; 4]; 4]> = vec![cgmath::Matrix4::identity().into(); MAX_JOINTS];
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Joint Buffer", name)),
+            contents: bytemuck::cast_slice(&identities),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some(name),
+        });
+
+        Self {
+            joints,
+            inverse_bind_matrices,
+            buffer,
+            bind_group,
+        }
+    }
+
+    /// Upload the joint matrices for the skeleton node transforms `world`.
+    pub(crate) fn update(&self, queue: &wgpu::Queue, world: &[cgmath::Matrix4<f32>]) {
+        if self.joints.is_empty() {
+            return;
+        }
+
+        let joint_matrices = self
+            .joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&node, inverse_bind)| (world[node] * inverse_bind).into())
+            .collect::<Vec<[[f32; 4]; 4]>>();
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&joint_matrices));
+    }
+
+    pub(crate) fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("skin_bind_group_layout"),
+        })
+    }
+}
@@ -8,6 +8,9 @@ pub struct ModelVertex {
     pub normal: [f32; 3],
     pub tangent: [f32; 3],
     pub bitangent: [f32; 3],
+    /// Skin joints deforming the vertex, only meaningful with non zero `weights`
+    pub joints: [u32; 4],
+    pub weights: [f32; 4],
 }
 
 impl Vertex for ModelVertex {
@@ -42,6 +45,17 @@ impl Vertex for ModelVertex {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                // Locations 5 to 11 are taken by the instance
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Uint32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 18]>() as wgpu::BufferAddress,
+                    shader_location: 13,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -0,0 +1,139 @@
+use crate::main_player::{error::PlayerErrorResult, wgpu_state::Vertex};
+
+use super::{
+    instance,
+    model::{self, MaterialKind},
+    post,
+    shader::{PipelineOptions, Shader},
+    texture,
+};
+
+/// Pipelines drawing the lights and the models into the HDR frame,
+/// rebuilt whenever the sample count of the frame changes.
+pub(crate) struct ScenePipelines {
+    model_shader: Shader,
+    pbr_shader: Shader,
+    light_shader: Shader,
+    model_layout: wgpu::PipelineLayout,
+    pbr_layout: wgpu::PipelineLayout,
+    light_layout: wgpu::PipelineLayout,
+    pub(crate) light: wgpu::RenderPipeline,
+    /// Opaque, then alpha blended without depth writes for materials with `blend`
+    model: [wgpu::RenderPipeline; 2],
+    pbr: [wgpu::RenderPipeline; 2],
+}
+
+impl ScenePipelines {
+    pub(crate) async fn new(
+        device: &wgpu::Device,
+        model_layouts: &model::ModelLayouts,
+        camera_layout: &wgpu::BindGroupLayout,
+        light_layout: &wgpu::BindGroupLayout,
+        sample_count: u32,
+    ) -> PlayerErrorResult<Self> {
+        let model_shader = Shader::from_file_name("Normal Shader", "bp.wgsl").await?;
+        let pbr_shader = Shader::from_file_name("PBR Shader", "pbr.wgsl").await?;
+        let light_shader = Shader::from_file_name("Light Shader", "pure.wgsl").await?;
+
+        let model_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[
+                &model_layouts.material,
+                camera_layout,
+                light_layout,
+                &model_layouts.skin,
+            ],
+            push_constant_ranges: &[],
+        });
+        let pbr_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("PBR Pipeline Layout"),
+            bind_group_layouts: &[
+                &model_layouts.pbr_material,
+                camera_layout,
+                light_layout,
+                &model_layouts.skin,
+            ],
+            push_constant_ranges: &[],
+        });
+        let light_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, light_layout],
+            push_constant_ranges: &[],
+        });
+
+        Ok(Self {
+            light: create_light_pipeline(device, &light_shader, &light_layout, sample_count),
+            model: create_model_pipelines(device, &model_shader, &model_layout, sample_count),
+            pbr: create_model_pipelines(device, &pbr_shader, &pbr_layout, sample_count),
+            model_shader,
+            pbr_shader,
+            light_shader,
+            model_layout,
+            pbr_layout,
+            light_layout,
+        })
+    }
+
+    pub(crate) fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.light =
+            create_light_pipeline(device, &self.light_shader, &self.light_layout, sample_count);
+        self.model =
+            create_model_pipelines(device, &self.model_shader, &self.model_layout, sample_count);
+        self.pbr = create_model_pipelines(device, &self.pbr_shader, &self.pbr_layout, sample_count);
+    }
+
+    /// Pipeline of the materials of `kind`, the transparent one for materials with `blend`.
+    pub(crate) fn model(&self, kind: MaterialKind, blend: bool) -> &wgpu::RenderPipeline {
+        let pipelines = match kind {
+            MaterialKind::Phong => &self.model,
+            MaterialKind::Pbr => &self.pbr,
+        };
+
+        &pipelines[blend as usize]
+    }
+}
+
+fn create_model_pipelines(
+    device: &wgpu::Device,
+    shader: &Shader,
+    layout: &wgpu::PipelineLayout,
+    sample_count: u32,
+) -> [wgpu::RenderPipeline; 2] {
+    let vertex_layouts = [
+        model::vertex::ModelVertex::desc(),
+        instance::InstanceRaw::desc(),
+    ];
+
+    [PipelineOptions::default(), PipelineOptions::transparent()].map(|options| {
+        shader.create_render_pipeline(
+            device,
+            layout,
+            post::HDR_FORMAT,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &vertex_layouts,
+            PipelineOptions {
+                sample_count,
+                ..options
+            },
+        )
+    })
+}
+
+fn create_light_pipeline(
+    device: &wgpu::Device,
+    shader: &Shader,
+    layout: &wgpu::PipelineLayout,
+    sample_count: u32,
+) -> wgpu::RenderPipeline {
+    shader.create_render_pipeline(
+        device,
+        layout,
+        post::HDR_FORMAT,
+        Some(texture::Texture::DEPTH_FORMAT),
+        &[model::vertex::ModelVertex::desc()],
+        PipelineOptions {
+            sample_count,
+            ..Default::default()
+        },
+    )
+}
@@ -0,0 +1,369 @@
+use serde::Deserialize;
+
+use crate::main_player::error::PlayerErrorResult;
+
+use super::texture;
+
+mod bloom;
+mod fxaa;
+mod ssao;
+mod tonemap;
+
+/// Format of the frame the scene is drawn into, before tonemapping.
+pub(crate) const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// How the drawn frame turns into the picture on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// Multiplies the colors before tonemapping
+    pub exposure: f32,
+    pub tonemapping: Tonemapping,
+    /// Glow around what is brighter than white, `None` turns it off
+    pub bloom: Option<BloomSettings>,
+    /// Darkens the creases of the models, `None` turns it off.
+    /// Needs the depth of the scene, which can't be sampled while multisampling,
+    /// so it only shows with an `msaa` of 1
+    pub ssao: Option<SsaoSettings>,
+    /// Samples per pixel of the scene, 1 turns multisampling off,
+    /// lowered to what the adapter supports. Above 1 `ssao` is off, the state warns once then
+    pub msaa: u32,
+    /// Smooths the edges of the tonemapped picture, cheaper than `msaa` and available on WebGL2
+    pub fxaa: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapping: Tonemapping::Aces,
+            bloom: Some(BloomSettings::default()),
+            ssao: None,
+            msaa: 1,
+            fxaa: false,
+        }
+    }
+}
+
+/// Curve mapping the unbounded colors of the frame into the range of the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tonemapping {
+    /// Clamps, everything brighter than white is white
+    None,
+    /// `c / (1 + c)`
+    Reinhard,
+    /// Filmic curve of ACES, as fitted by Narkowicz
+    #[default]
+    Aces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BloomSettings {
+    /// Brightness where colors start to glow, fading in from half of it
+    pub threshold: f32,
+    /// Strength of the glow added to the frame
+    pub intensity: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            intensity: 0.5,
+        }
+    }
+}
+
+/// Screen space ambient occlusion, from the depth of the scene.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SsaoSettings {
+    /// Distance around a point searched for what hides it, in world units
+    pub radius: f32,
+    /// Strength of the darkening, 0 leaves the frame as it is
+    pub intensity: f32,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Sample counts of [`RenderSettings::msaa`] the adapter can draw the frame with, ascending.
+///
+/// Only 4 is guaranteed besides 1, the features of a format don't tell the other counts.
+/// WebGL2 has no multisampled float targets without extensions, so the HDR frame stays at 1 there.
+pub(crate) fn supported_sample_counts(adapter: &wgpu::Adapter) -> Vec<u32> {
+    use wgpu::TextureFormatFeatureFlags as Flags;
+
+    let multisampled = [
+        (HDR_FORMAT, Flags::MULTISAMPLE | Flags::MULTISAMPLE_RESOLVE),
+        (texture::Texture::DEPTH_FORMAT, Flags::MULTISAMPLE),
+    ]
+    .iter()
+    .all(|&(format, flags)| {
+        adapter
+            .get_texture_format_features(format)
+            .flags
+            .contains(flags)
+    });
+
+    if multisampled {
+        vec![1, 4]
+    } else {
+        vec![1]
+    }
+}
+
+/// What every [`PostEffect`] runs with.
+pub(crate) struct PostContext<'a> {
+    pub(crate) device: &'a wgpu::Device,
+    pub(crate) queue: &'a wgpu::Queue,
+    pub(crate) settings: &'a RenderSettings,
+    /// Depth of the scene, `None` when it is multisampled
+    pub(crate) depth: Option<&'a wgpu::TextureView>,
+    /// Projection the scene was drawn with
+    pub(crate) projection: cgmath::Matrix4<f32>,
+}
+
+/// Full screen pass of the [`PostChain`] on the HDR frame, before tonemapping.
+pub(crate) trait PostEffect {
+    /// Skipped effects leave the frame as it is.
+    fn enabled(&self, context: &PostContext) -> bool;
+
+    /// Follow the size of the frame.
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32);
+
+    /// Draw `input` with the effect into `output`, both of [`HDR_FORMAT`] and the size of the frame.
+    fn run(
+        &self,
+        context: &PostContext,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    );
+}
+
+/// Frame the scene is drawn into and the effects taking it to the target.
+pub(crate) struct PostChain {
+    settings: RenderSettings,
+    /// The frame and the one effects draw into, swapped after every effect
+    frames: [(wgpu::Texture, wgpu::TextureView); 2],
+    /// Drawn into instead of the frame and resolved into it when multisampling
+    msaa_frame: Option<(wgpu::Texture, wgpu::TextureView)>,
+    sample_count: u32,
+    size: (u32, u32),
+    effects: Vec<Box<dyn PostEffect>>,
+    tonemap: tonemap::Tonemap,
+    fxaa: fxaa::Fxaa,
+}
+
+impl PostChain {
+    pub(crate) async fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> PlayerErrorResult<Self> {
+        let effects: Vec<Box<dyn PostEffect>> = vec![
+            Box::new(ssao::Ssao::new(device, width, height).await?),
+            Box::new(bloom::Bloom::new(device, width, height).await?),
+        ];
+
+        Ok(Self {
+            settings: RenderSettings::default(),
+            frames: Self::create_frames(device, width, height),
+            msaa_frame: None,
+            sample_count: 1,
+            size: (width, height),
+            effects,
+            tonemap: tonemap::Tonemap::new(device, target_format).await?,
+            fxaa: fxaa::Fxaa::new(device, target_format, width, height).await?,
+        })
+    }
+
+    fn create_frames(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> [(wgpu::Texture, wgpu::TextureView); 2] {
+        [
+            create_target(device, "HDR Frame", HDR_FORMAT, width, height),
+            create_target(device, "HDR Frame Swap", HDR_FORMAT, width, height),
+        ]
+    }
+
+    pub(crate) fn settings(&self) -> RenderSettings {
+        self.settings
+    }
+
+    pub(crate) fn set_settings(&mut self, settings: RenderSettings) {
+        self.settings = settings;
+    }
+
+    pub(crate) fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Draw the scene with `sample_count` samples per pixel from now on.
+    pub(crate) fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.msaa_frame = self.create_msaa_frame(device);
+    }
+
+    fn create_msaa_frame(
+        &self,
+        device: &wgpu::Device,
+    ) -> Option<(wgpu::Texture, wgpu::TextureView)> {
+        if self.sample_count <= 1 {
+            return None;
+        }
+
+        let (width, height) = self.size;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HDR Multisampled Frame"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: self.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            // Multisampled textures can only be drawn into on WebGL
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Some((texture, view))
+    }
+
+    /// View and resolve target the scene is drawn into, in [`HDR_FORMAT`].
+    pub(crate) fn frame_attachment(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.msaa_frame {
+            Some((_, view)) => (view, Some(&self.frames[0].1)),
+            None => (&self.frames[0].1, None),
+        }
+    }
+
+    pub(crate) fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.size = (width, height);
+        self.frames = Self::create_frames(device, width, height);
+        self.msaa_frame = self.create_msaa_frame(device);
+        for effect in &mut self.effects {
+            effect.resize(device, width, height);
+        }
+        self.fxaa.resize(device, width, height);
+    }
+
+    /// Run the enabled effects on the frame, then tonemap it into `target`,
+    /// through the FXAA pass when it is on.
+    ///
+    /// `depth` and `projection` are the ones the scene was drawn with.
+    pub(crate) fn run(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        depth: Option<&wgpu::TextureView>,
+        projection: cgmath::Matrix4<f32>,
+        target: &wgpu::TextureView,
+    ) {
+        let context = PostContext {
+            device,
+            queue,
+            settings: &self.settings,
+            depth,
+            projection,
+        };
+
+        let mut input = 0;
+        for effect in self.effects.iter().filter(|e| e.enabled(&context)) {
+            effect.run(
+                &context,
+                encoder,
+                &self.frames[input].1,
+                &self.frames[1 - input].1,
+            );
+            input = 1 - input;
+        }
+
+        if self.settings.fxaa {
+            self.tonemap
+                .run(&context, encoder, &self.frames[input].1, self.fxaa.input());
+            self.fxaa.run(encoder, target);
+        } else {
+            self.tonemap
+                .run(&context, encoder, &self.frames[input].1, target);
+        }
+    }
+}
+
+/// Texture drawn into and sampled by the passes, of [`HDR_FORMAT`] before tonemapping.
+fn create_target(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
+
+fn create_sampler(device: &wgpu::Device, label: &str) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(label),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+/// One triangle of `pipeline` over the whole `output`.
+fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    output: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        })],
+        depth_stencil_attachment: None,
+    });
+
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
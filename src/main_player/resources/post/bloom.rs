@@ -0,0 +1,251 @@
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::main_player::{error::PlayerErrorResult, resources::shader};
+
+use super::{PostContext, PostEffect, HDR_FORMAT};
+
+/// Halvings of the frame the glow is blurred over, the more the wider it spreads.
+const MAX_LEVELS: u32 = 6;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    intensity: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 2],
+}
+
+/// Bright parts of the frame, downsampled into a chain of halved textures
+/// and summed back up over each other.
+pub(super) struct Bloom {
+    layout: wgpu::BindGroupLayout,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    /// Half the size of the frame first
+    levels: Vec<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl Bloom {
+    pub(super) async fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> PlayerErrorResult<Self> {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("bloom_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("Bloom Shader", "bloom.wgsl").await?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point, blend| {
+            shader.create_fullscreen_pipeline(
+                device,
+                &pipeline_layout,
+                entry_point,
+                HDR_FORMAT,
+                None,
+                shader::PipelineOptions {
+                    blend,
+                    ..Default::default()
+                },
+            )
+        };
+        // Upsampled levels add onto the downsampled ones, the alpha stays
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bloom Buffer"),
+            contents: bytemuck::cast_slice(&[BloomUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Ok(Self {
+            prefilter_pipeline: pipeline("fs_prefilter", None),
+            downsample_pipeline: pipeline("fs_downsample", None),
+            upsample_pipeline: pipeline("fs_upsample", Some(additive)),
+            composite_pipeline: pipeline("fs_composite", None),
+            layout,
+            buffer,
+            sampler: super::create_sampler(device, "Bloom Sampler"),
+            levels: Self::create_levels(device, width, height),
+        })
+    }
+
+    fn create_levels(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> Vec<(wgpu::Texture, wgpu::TextureView)> {
+        (1..=MAX_LEVELS)
+            .take_while(|level| width.min(height) >> level > 0)
+            .map(|level| {
+                super::create_target(
+                    device,
+                    &format!("Bloom Level {}", level),
+                    HDR_FORMAT,
+                    width >> level,
+                    height >> level,
+                )
+            })
+            .collect()
+    }
+
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        source: &wgpu::TextureView,
+        scene: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(scene),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("bloom_bind_group"),
+        })
+    }
+}
+
+impl PostEffect for Bloom {
+    fn enabled(&self, context: &PostContext) -> bool {
+        context.settings.bloom.is_some() && !self.levels.is_empty()
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.levels = Self::create_levels(device, width, height);
+    }
+
+    fn run(
+        &self,
+        context: &PostContext,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let settings = context.settings.bloom.unwrap_or_default();
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[BloomUniform {
+                threshold: settings.threshold,
+                intensity: settings.intensity,
+                _padding: [0; 2],
+            }]),
+        );
+
+        let device = context.device;
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT);
+        let views = self.levels.iter().map(|(_, view)| view).collect::<Vec<_>>();
+
+        // Only what is bright enough into the first level, then down the chain
+        let bind_group = self.bind_group(device, input, input);
+        super::draw_fullscreen(
+            encoder,
+            "Bloom Prefilter",
+            &self.prefilter_pipeline,
+            &bind_group,
+            views[0],
+            clear,
+        );
+        for pair in views.windows(2) {
+            let bind_group = self.bind_group(device, pair[0], input);
+            super::draw_fullscreen(
+                encoder,
+                "Bloom Downsample",
+                &self.downsample_pipeline,
+                &bind_group,
+                pair[1],
+                clear,
+            );
+        }
+
+        // Back up, every level gathers the blur of all smaller ones
+        for pair in views.windows(2).rev() {
+            let bind_group = self.bind_group(device, pair[1], input);
+            super::draw_fullscreen(
+                encoder,
+                "Bloom Upsample",
+                &self.upsample_pipeline,
+                &bind_group,
+                pair[0],
+                wgpu::LoadOp::Load,
+            );
+        }
+
+        let bind_group = self.bind_group(device, views[0], input);
+        super::draw_fullscreen(
+            encoder,
+            "Bloom Composite",
+            &self.composite_pipeline,
+            &bind_group,
+            output,
+            clear,
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::main_player::{error::PlayerErrorResult, resources::shader};
+
+/// Fast approximate anti-aliasing of the tonemapped picture,
+/// blurring the edges it finds in the luma along their direction.
+pub(super) struct Fxaa {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    /// Tonemapped picture, in the format of the target
+    input: (wgpu::Texture, wgpu::TextureView),
+    bind_group: wgpu::BindGroup,
+}
+
+impl Fxaa {
+    pub(super) async fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> PlayerErrorResult<Self> {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("fxaa_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("FXAA Shader", "fxaa.wgsl").await?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("FXAA Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = shader.create_fullscreen_pipeline(
+            device,
+            &pipeline_layout,
+            "fs_main",
+            target_format,
+            None,
+            shader::PipelineOptions::default(),
+        );
+
+        let sampler = super::create_sampler(device, "FXAA Sampler");
+        let input = super::create_target(device, "FXAA Input", target_format, width, height);
+        let bind_group = Self::create_bind_group(device, &layout, &input.1, &sampler);
+
+        Ok(Self {
+            layout,
+            pipeline,
+            sampler,
+            format: target_format,
+            input,
+            bind_group,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        input: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("fxaa_bind_group"),
+        })
+    }
+
+    /// Where the picture is tonemapped into before the pass.
+    pub(super) fn input(&self) -> &wgpu::TextureView {
+        &self.input.1
+    }
+
+    pub(super) fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.input = super::create_target(device, "FXAA Input", self.format, width, height);
+        self.bind_group =
+            Self::create_bind_group(device, &self.layout, &self.input.1, &self.sampler);
+    }
+
+    pub(super) fn run(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        super::draw_fullscreen(
+            encoder,
+            "FXAA Pass",
+            &self.pipeline,
+            &self.bind_group,
+            target,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+        );
+    }
+}
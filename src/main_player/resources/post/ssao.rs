@@ -0,0 +1,243 @@
+use bytemuck::Zeroable;
+use cgmath::SquareMatrix;
+use wgpu::util::DeviceExt;
+
+use crate::main_player::{error::PlayerErrorResult, resources::shader};
+
+use super::{PostContext, PostEffect, HDR_FORMAT};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SsaoUniform {
+    projection: [[f32; 4]; 4],
+    inverse_projection: [[f32; 4]; 4],
+    radius: f32,
+    intensity: f32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 2],
+}
+
+/// Ambient occlusion of the points of the depth, drawn into a texture of its own
+/// and blurred while darkening the frame with it.
+pub(super) struct Ssao {
+    occlusion_layout: wgpu::BindGroupLayout,
+    composite_layout: wgpu::BindGroupLayout,
+    occlusion_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    /// How much of the light reaches every pixel, before the blur
+    occlusion: (wgpu::Texture, wgpu::TextureView),
+}
+
+impl Ssao {
+    const OCCLUSION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+
+    pub(super) async fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> PlayerErrorResult<Self> {
+        let uniform_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let occlusion_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        // Depth textures only compare in GLSL, read as floats instead
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("ssao_occlusion_bind_group_layout"),
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                texture_entry(2),
+                texture_entry(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("ssao_composite_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("SSAO Shader", "ssao.wgsl").await?;
+        let pipeline = |label, layout, entry_point, format| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            shader.create_fullscreen_pipeline(
+                device,
+                &pipeline_layout,
+                entry_point,
+                format,
+                None,
+                shader::PipelineOptions::default(),
+            )
+        };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("SSAO Buffer"),
+            contents: bytemuck::cast_slice(&[SsaoUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Ok(Self {
+            occlusion_pipeline: pipeline(
+                "SSAO Occlusion Pipeline Layout",
+                &occlusion_layout,
+                "fs_occlusion",
+                Self::OCCLUSION_FORMAT,
+            ),
+            composite_pipeline: pipeline(
+                "SSAO Composite Pipeline Layout",
+                &composite_layout,
+                "fs_composite",
+                HDR_FORMAT,
+            ),
+            occlusion_layout,
+            composite_layout,
+            buffer,
+            sampler: super::create_sampler(device, "SSAO Sampler"),
+            occlusion: Self::create_occlusion(device, width, height),
+        })
+    }
+
+    fn create_occlusion(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        super::create_target(
+            device,
+            "SSAO Occlusion",
+            Self::OCCLUSION_FORMAT,
+            width,
+            height,
+        )
+    }
+}
+
+impl PostEffect for Ssao {
+    fn enabled(&self, context: &PostContext) -> bool {
+        context.settings.ssao.is_some() && context.depth.is_some()
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.occlusion = Self::create_occlusion(device, width, height);
+    }
+
+    fn run(
+        &self,
+        context: &PostContext,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let (settings, depth) = match (context.settings.ssao, context.depth) {
+            (Some(settings), Some(depth)) => (settings, depth),
+            _ => return,
+        };
+        let inverse_projection = context
+            .projection
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity);
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[SsaoUniform {
+                projection: context.projection.into(),
+                inverse_projection: inverse_projection.into(),
+                radius: settings.radius,
+                intensity: settings.intensity,
+                _padding: [0; 2],
+            }]),
+        );
+
+        let device = context.device;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.occlusion_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+            label: Some("ssao_occlusion_bind_group"),
+        });
+        super::draw_fullscreen(
+            encoder,
+            "SSAO Occlusion",
+            &self.occlusion_pipeline,
+            &bind_group,
+            &self.occlusion.1,
+            wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&self.occlusion.1),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("ssao_composite_bind_group"),
+        });
+        super::draw_fullscreen(
+            encoder,
+            "SSAO Composite",
+            &self.composite_pipeline,
+            &bind_group,
+            output,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+        );
+    }
+}
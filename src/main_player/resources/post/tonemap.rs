@@ -0,0 +1,144 @@
+use bytemuck::Zeroable;
+use wgpu::util::DeviceExt;
+
+use crate::main_player::{error::PlayerErrorResult, resources::shader};
+
+use super::{PostContext, Tonemapping};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    exposure: f32,
+    /// 0 clamps, 1 Reinhard, 2 ACES
+    curve: u32,
+    // Due to uniforms requiring 16 byte (4 float) spacing, we need to use a padding field here
+    _padding: [u32; 2],
+}
+
+/// Last pass of the chain, from the HDR frame into the target.
+pub(super) struct Tonemap {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+}
+
+impl Tonemap {
+    pub(super) async fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+    ) -> PlayerErrorResult<Self> {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("tonemap_bind_group_layout"),
+        });
+
+        let shader = shader::Shader::from_file_name("Tonemap Shader", "tonemap.wgsl").await?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = shader.create_fullscreen_pipeline(
+            device,
+            &pipeline_layout,
+            "fs_main",
+            target_format,
+            None,
+            shader::PipelineOptions::default(),
+        );
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[TonemapUniform::zeroed()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Ok(Self {
+            layout,
+            pipeline,
+            buffer,
+            sampler: super::create_sampler(device, "Tonemap Sampler"),
+        })
+    }
+
+    pub(super) fn run(
+        &self,
+        context: &PostContext,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+    ) {
+        let curve = match context.settings.tonemapping {
+            Tonemapping::None => 0,
+            Tonemapping::Reinhard => 1,
+            Tonemapping::Aces => 2,
+        };
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[TonemapUniform {
+                exposure: context.settings.exposure,
+                curve,
+                _padding: [0; 2],
+            }]),
+        );
+
+        let bind_group = context
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: Some("tonemap_bind_group"),
+            });
+
+        super::draw_fullscreen(
+            encoder,
+            "Tonemap Pass",
+            &self.pipeline,
+            &bind_group,
+            target,
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+        );
+    }
+}
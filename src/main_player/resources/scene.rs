@@ -0,0 +1,382 @@
+use cgmath::{EuclideanSpace, SquareMatrix, Transform};
+
+use super::model::skeleton::NodeTransform;
+
+/// Handle of a node inside its [`Scene`].
+///
+/// Slots of removed nodes are reused, the generation keeps ids of removed nodes
+/// from reaching the nodes added after them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+impl NodeId {
+    /// Slot of the node, the index into [`Scene::world_matrices`].
+    pub fn index(self) -> usize {
+        self.index
+    }
+}
+
+/// What a node places in the world.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeContent {
+    /// Key of a model loaded by [`State::load_model`](crate::main_player::State::load_model)
+    Model(String),
+    /// The light of the state at this index sits at the origin of the node
+    /// and shines down its -z axis
+    Light(usize),
+    /// The camera sits at the origin of the node and looks down its -z axis
+    Camera,
+}
+
+#[derive(Debug, Clone)]
+pub struct SceneNode {
+    pub name: String,
+    /// Relative to the parent
+    pub transform: NodeTransform,
+    pub content: Option<NodeContent>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl SceneNode {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Slot {
+    /// Counts the nodes removed from this slot
+    generation: u32,
+    node: Option<SceneNode>,
+}
+
+/// Hierarchy of nodes, the transform of a node applies to all of its descendants.
+#[derive(Debug, Clone, Default)]
+pub struct Scene {
+    slots: Vec<Slot>,
+}
+
+impl Scene {
+    /// Add a node under `parent`, or as a root without one.
+    /// `None` if `parent` is not a node of the scene.
+    pub fn add_node(
+        &mut self,
+        parent: Option<NodeId>,
+        name: &str,
+        transform: NodeTransform,
+        content: Option<NodeContent>,
+    ) -> Option<NodeId> {
+        if parent.is_some_and(|p| self.node(p).is_none()) {
+            return None;
+        }
+
+        let node = SceneNode {
+            name: name.to_string(),
+            transform,
+            content,
+            parent,
+            children: Vec::new(),
+        };
+
+        let index = match self.slots.iter().position(|s| s.node.is_none()) {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot::default());
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.node = Some(node);
+        let id = NodeId {
+            index,
+            generation: slot.generation,
+        };
+
+        if let Some(parent) = parent.and_then(|p| self.node_mut(p)) {
+            parent.children.push(id);
+        }
+
+        Some(id)
+    }
+
+    /// Remove a node together with all of its descendants.
+    pub fn remove_node(&mut self, id: NodeId) -> Option<SceneNode> {
+        self.node(id)?;
+        let slot = &mut self.slots[id.index];
+        let node = slot.node.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+
+        if let Some(parent) = node.parent.and_then(|p| self.node_mut(p)) {
+            parent.children.retain(|&c| c != id);
+        }
+        for &child in &node.children {
+            // Detach first, so the child doesn't look for this node again
+            if let Some(child) = self.node_mut(child) {
+                child.parent = None;
+            }
+            self.remove_node(child);
+        }
+
+        Some(node)
+    }
+
+    /// Move a node under `parent`, or make it a root.
+    /// Returns false and changes nothing if either is not a node of the scene
+    /// or the node would become its own ancestor.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        if self.node(id).is_none()
+            || parent.is_some_and(|p| self.node(p).is_none() || self.is_ancestor(id, p))
+        {
+            return false;
+        }
+
+        if let Some(old) = self.node(id).and_then(|n| n.parent) {
+            if let Some(old) = self.node_mut(old) {
+                old.children.retain(|&c| c != id);
+            }
+        }
+        if let Some(parent) = parent.and_then(|p| self.node_mut(p)) {
+            parent.children.push(id);
+        }
+        if let Some(node) = self.node_mut(id) {
+            node.parent = parent;
+        }
+
+        true
+    }
+
+    /// Whether `ancestor` is `id` or one of the nodes above it.
+    fn is_ancestor(&self, ancestor: NodeId, id: NodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(c) = current {
+            if c == ancestor {
+                return true;
+            }
+            current = self.node(c).and_then(|n| n.parent);
+        }
+
+        false
+    }
+
+    /// `None` for removed nodes.
+    pub fn node(&self, id: NodeId) -> Option<&SceneNode> {
+        let slot = self.slots.get(id.index)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Option<&mut SceneNode> {
+        let slot = self.slots.get_mut(id.index)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+
+    /// First node called `name`.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|(_, n)| n.name == name).map(|(id, _)| id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &SceneNode)> {
+        self.slots.iter().enumerate().filter_map(|(index, s)| {
+            let id = NodeId {
+                index,
+                generation: s.generation,
+            };
+            Some((id, s.node.as_ref()?))
+        })
+    }
+
+    /// Transform from node space to world space of every node, indexed by [`NodeId::index`].
+    pub fn world_matrices(&self) -> Vec<cgmath::Matrix4<f32>> {
+        let mut world = vec![cgmath::Matrix4::identity(); self.slots.len()];
+
+        let mut stack = self
+            .iter()
+            .filter(|(_, n)| n.parent.is_none())
+            .map(|(id, _)| (id, cgmath::Matrix4::identity()))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = match self.node(id) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            let matrix = parent_world * node.transform.matrix();
+            world[id.index] = matrix;
+            stack.extend(node.children.iter().map(|&c| (c, matrix)));
+        }
+
+        world
+    }
+
+    /// Every node holding `content`, with its world matrix.
+    pub(crate) fn placements<'a>(
+        &'a self,
+        world: &'a [cgmath::Matrix4<f32>],
+    ) -> impl Iterator<Item = (&'a NodeContent, cgmath::Matrix4<f32>)> + 'a {
+        self.iter()
+            .filter_map(move |(id, n)| Some((n.content.as_ref()?, world[id.index])))
+    }
+}
+
+/// Position and the point 1 ahead on the -z axis of a node in world space.
+pub(crate) fn eye_and_target(
+    world: cgmath::Matrix4<f32>,
+) -> (cgmath::Point3<f32>, cgmath::Point3<f32>) {
+    (
+        world.transform_point(cgmath::Point3::origin()),
+        world.transform_point(cgmath::Point3::new(0.0, 0.0, -1.0)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> NodeTransform {
+        NodeTransform {
+            translation: cgmath::Vector3::new(x, y, z),
+            ..Default::default()
+        }
+    }
+
+    fn origin(world: &[cgmath::Matrix4<f32>], id: NodeId) -> cgmath::Point3<f32> {
+        world[id.index()].transform_point(cgmath::Point3::origin())
+    }
+
+    #[test]
+    fn add_and_remove_nodes() {
+        let mut scene = Scene::default();
+        let root = scene
+            .add_node(None, "root", NodeTransform::default(), None)
+            .unwrap();
+        let child = scene
+            .add_node(Some(root), "child", NodeTransform::default(), None)
+            .unwrap();
+        let grandchild = scene
+            .add_node(Some(child), "grandchild", NodeTransform::default(), None)
+            .unwrap();
+
+        assert_eq!(scene.node(root).unwrap().children(), &[child]);
+        assert_eq!(scene.node(grandchild).unwrap().parent(), Some(child));
+        assert_eq!(scene.find("grandchild"), Some(grandchild));
+
+        assert!(scene.remove_node(child).is_some());
+        assert!(scene.node(child).is_none());
+        assert!(scene.node(grandchild).is_none());
+        assert!(scene.node(root).unwrap().children().is_empty());
+        assert!(scene.remove_node(child).is_none());
+        assert_eq!(scene.iter().count(), 1);
+    }
+
+    #[test]
+    fn stale_ids_miss_reused_slots() {
+        let mut scene = Scene::default();
+        let old = scene
+            .add_node(None, "old", NodeTransform::default(), None)
+            .unwrap();
+        scene.remove_node(old);
+
+        let new = scene
+            .add_node(None, "new", NodeTransform::default(), None)
+            .unwrap();
+        assert_eq!(old.index(), new.index());
+        assert!(scene.node(old).is_none());
+        assert!(scene
+            .add_node(Some(old), "orphan", NodeTransform::default(), None)
+            .is_none());
+        assert!(!scene.set_parent(new, Some(old)));
+        assert!(scene.remove_node(old).is_none());
+        assert_eq!(scene.node(new).unwrap().name, "new");
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles_and_missing_nodes() {
+        let mut scene = Scene::default();
+        let a = scene
+            .add_node(None, "a", NodeTransform::default(), None)
+            .unwrap();
+        let b = scene
+            .add_node(Some(a), "b", NodeTransform::default(), None)
+            .unwrap();
+        let c = scene
+            .add_node(None, "c", NodeTransform::default(), None)
+            .unwrap();
+
+        assert!(!scene.set_parent(a, Some(b)));
+        assert!(!scene.set_parent(a, Some(a)));
+        let missing = NodeId {
+            index: 10,
+            generation: 0,
+        };
+        assert!(!scene.set_parent(a, Some(missing)));
+        assert!(!scene.set_parent(missing, Some(a)));
+
+        assert!(scene.set_parent(b, Some(c)));
+        assert!(scene.node(a).unwrap().children().is_empty());
+        assert_eq!(scene.node(c).unwrap().children(), &[b]);
+
+        assert!(scene.set_parent(b, None));
+        assert_eq!(scene.node(b).unwrap().parent(), None);
+        assert!(scene.node(c).unwrap().children().is_empty());
+    }
+
+    #[test]
+    fn world_matrices_follow_the_parents() {
+        let mut scene = Scene::default();
+        let root = scene
+            .add_node(None, "root", translation(1.0, 0.0, 0.0), None)
+            .unwrap();
+        let child = scene
+            .add_node(Some(root), "child", translation(0.0, 2.0, 0.0), None)
+            .unwrap();
+        let other = scene
+            .add_node(None, "other", translation(0.0, 0.0, 3.0), None)
+            .unwrap();
+
+        let world = scene.world_matrices();
+        assert_eq!(origin(&world, root), cgmath::Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(origin(&world, child), cgmath::Point3::new(1.0, 2.0, 0.0));
+        assert_eq!(origin(&world, other), cgmath::Point3::new(0.0, 0.0, 3.0));
+
+        scene.set_parent(child, Some(other));
+        let world = scene.world_matrices();
+        assert_eq!(origin(&world, child), cgmath::Point3::new(0.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn placements_of_nodes_with_content() {
+        let mut scene = Scene::default();
+        let content = Some(NodeContent::Model("model".to_owned()));
+        scene.add_node(None, "empty", NodeTransform::default(), None);
+        scene.add_node(None, "model", translation(0.0, 1.0, 0.0), content);
+
+        let world = scene.world_matrices();
+        let placements = scene.placements(&world).collect::<Vec<_>>();
+        assert_eq!(placements.len(), 1);
+        assert_eq!(placements[0].0, &NodeContent::Model("model".to_owned()));
+        assert_eq!(
+            placements[0].1.transform_point(cgmath::Point3::origin()),
+            cgmath::Point3::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn eye_and_target_look_down_negative_z() {
+        let (eye, target) = eye_and_target(translation(1.0, 0.0, 0.0).matrix());
+        assert_eq!(eye, cgmath::Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(target, cgmath::Point3::new(1.0, 0.0, -1.0));
+    }
+}
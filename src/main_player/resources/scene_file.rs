@@ -0,0 +1,851 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+use crate::{
+    main_player::{
+        error::{MainPlayerError, PlayerErrorResult},
+        wgpu_state::State,
+    },
+    requests,
+};
+
+use super::{
+    animation::{
+        AnimationClip, AnimationPlayer, Easing, Interpolation, Keyframes, LoopMode, NodeTrack,
+        Track,
+    },
+    background::Background,
+    camera::{Camera, Projection},
+    light::{Light, LightKind, ShadowSettings, SPOT_SHADOW_NEAR},
+    model::skeleton::NodeTransform,
+    post::RenderSettings,
+    scene::{NodeContent, NodeId, Scene},
+};
+
+const SCENE_DIR: &str = "/static/scene";
+
+/// Everything a player shows, as written in a scene file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    /// Model key to file name, see [`State::load_model`]
+    #[serde(default)]
+    models: BTreeMap<String, String>,
+    #[serde(default)]
+    nodes: Vec<NodeDesc>,
+    /// Replace the lights of the state
+    lights: Option<Vec<LightDesc>>,
+    camera: Option<CameraDesc>,
+    environment: Option<EnvironmentDesc>,
+    background: Option<BackgroundDesc>,
+    render: Option<RenderSettings>,
+    #[serde(default)]
+    animations: Vec<AnimationDesc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TransformDesc {
+    translation: Option<[f32; 3]>,
+    /// Quaternion as `[x, y, z, w]`, like glTF
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+}
+
+impl From<&TransformDesc> for NodeTransform {
+    fn from(desc: &TransformDesc) -> Self {
+        let identity = NodeTransform::default();
+
+        NodeTransform {
+            translation: desc.translation.map_or(identity.translation, Into::into),
+            rotation: desc.rotation.map_or(identity.rotation, |[x, y, z, w]| {
+                cgmath::Quaternion::new(w, x, y, z)
+            }),
+            scale: desc.scale.map_or(identity.scale, Into::into),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum ContentDesc {
+    Model(String),
+    /// Index into `lights`
+    Light(usize),
+    Camera,
+}
+
+impl From<&ContentDesc> for NodeContent {
+    fn from(desc: &ContentDesc) -> Self {
+        match desc {
+            ContentDesc::Model(key) => NodeContent::Model(key.clone()),
+            ContentDesc::Light(index) => NodeContent::Light(*index),
+            ContentDesc::Camera => NodeContent::Camera,
+        }
+    }
+}
+
+/// The transform is spelled out instead of a flattened [`TransformDesc`],
+/// serde can't deny unknown fields next to flattened ones.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeDesc {
+    name: String,
+    translation: Option<[f32; 3]>,
+    /// Quaternion as `[x, y, z, w]`, like glTF
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    content: Option<ContentDesc>,
+    /// More copies of the content, relative to this node
+    #[serde(default)]
+    instances: Vec<TransformDesc>,
+    #[serde(default)]
+    children: Vec<NodeDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LightDesc {
+    #[serde(default)]
+    kind: LightKind,
+    position: Option<[f32; 3]>,
+    direction: Option<[f32; 3]>,
+    color: Option<[f32; 3]>,
+    intensity: Option<f32>,
+    range: Option<f32>,
+    /// Degrees
+    inner_cone: Option<f32>,
+    /// Degrees
+    outer_cone: Option<f32>,
+    /// Only directional and spot lights cast shadows
+    shadow: Option<ShadowSettings>,
+}
+
+impl From<&LightDesc> for Light {
+    fn from(desc: &LightDesc) -> Self {
+        let default = Light::default();
+
+        Light {
+            kind: desc.kind,
+            position: desc.position.map_or(default.position, Into::into),
+            direction: desc.direction.map_or(default.direction, Into::into),
+            color: desc.color.unwrap_or(default.color),
+            intensity: desc.intensity.unwrap_or(default.intensity),
+            range: desc.range,
+            inner_cone: desc.inner_cone.map_or(default.inner_cone, f32::to_radians),
+            outer_cone: desc.outer_cone.map_or(default.outer_cone, f32::to_radians),
+            shadow: desc.shadow,
+        }
+    }
+}
+
+/// Linear rgba colors, images inside `static/image`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BackgroundDesc {
+    Color([f64; 4]),
+    Kind(BackgroundKindDesc),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundKindDesc {
+    Color([f64; 4]),
+    Gradient {
+        top: [f64; 4],
+        bottom: [f64; 4],
+    },
+    Image(String),
+    /// Faces +x, -x, +y, -y, +z, -z
+    Skybox([String; 6]),
+    Environment,
+    Transparent,
+}
+
+impl From<&BackgroundDesc> for Background {
+    fn from(desc: &BackgroundDesc) -> Self {
+        let color = |[r, g, b, a]: [f64; 4]| wgpu::Color { r, g, b, a };
+
+        match desc {
+            BackgroundDesc::Color(c) => Background::Color(color(*c)),
+            BackgroundDesc::Kind(kind) => match kind {
+                BackgroundKindDesc::Color(c) => Background::Color(color(*c)),
+                BackgroundKindDesc::Gradient { top, bottom } => Background::Gradient {
+                    top: color(*top),
+                    bottom: color(*bottom),
+                },
+                BackgroundKindDesc::Image(name) => Background::Image(name.clone()),
+                BackgroundKindDesc::Skybox(faces) => Background::Skybox(faces.clone()),
+                BackgroundKindDesc::Environment => Background::Environment,
+                BackgroundKindDesc::Transparent => Background::Transparent,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    /// Equirectangular `.hdr` inside `static/image`
+    file: String,
+    intensity: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    eye: Option<[f32; 3]>,
+    target: Option<[f32; 3]>,
+    up: Option<[f32; 3]>,
+    /// Degrees
+    fovy: Option<f32>,
+    znear: Option<f32>,
+    zfar: Option<f32>,
+    projection: Option<Projection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnimationDesc {
+    name: String,
+    #[serde(flatten)]
+    source: ClipSource,
+    #[serde(default, rename = "loop")]
+    loop_mode: LoopMode,
+    #[serde(default = "default_true")]
+    autoplay: bool,
+    speed: Option<f32>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ClipSource {
+    Tracks(Vec<TrackDesc>),
+    /// Animation of a loaded model, by name
+    ModelClip {
+        model: String,
+        clip: String,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TrackDesc {
+    target: TargetDesc,
+    /// Index into `lights` for light targets
+    #[serde(default)]
+    light: usize,
+    times: Vec<f32>,
+    /// Vectors for positions, colors and scales, `[x, y, z, w]` for rotations
+    values: Vec<Vec<f32>>,
+    #[serde(default)]
+    interpolation: Interpolation,
+    #[serde(default)]
+    easing: Easing,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum TargetDesc {
+    LightPosition,
+    LightColor,
+    CameraEye,
+    CameraTarget,
+    /// Scene node by name
+    Node {
+        node: String,
+        property: PropertyDesc,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PropertyDesc {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+impl State {
+    /// Replace the scene, its models and the animations with the ones of a json file in `static/scene`.
+    ///
+    /// Everything the file names is loaded first, if any of it fails the current scene stays as it is.
+    pub async fn load_scene(&mut self, file_name: &str) -> PlayerErrorResult<()> {
+        let text = requests::request_string(&format!("{}/{}", SCENE_DIR, file_name)).await?;
+        let desc: SceneDesc = serde_json::from_str(&text)?;
+
+        let mut models = HashMap::new();
+        for (key, file_name) in &desc.models {
+            models.insert(key.clone(), self.request_model(key, file_name).await?);
+        }
+
+        let mut scene = Scene::default();
+        for node in &desc.nodes {
+            add_node(&mut scene, None, node);
+        }
+
+        let mut animations = Vec::new();
+        for animation_desc in &desc.animations {
+            let clip = match &animation_desc.source {
+                ClipSource::Tracks(tracks) => AnimationClip::new(
+                    &animation_desc.name,
+                    tracks
+                        .iter()
+                        .map(load_track)
+                        .collect::<PlayerErrorResult<_>>()?,
+                ),
+                ClipSource::ModelClip { model, clip } => models
+                    .get(model)
+                    .and_then(|m| m.animations.iter().find(|c| &c.name == clip))
+                    .cloned()
+                    .ok_or_else(|| {
+                        MainPlayerError::SceneError(format!("model {} has no clip {}", model, clip))
+                    })?,
+            };
+
+            let mut animation = AnimationPlayer::new(clip, animation_desc.loop_mode);
+            animation.speed = animation_desc.speed.unwrap_or(1.0);
+            if animation_desc.autoplay {
+                animation.play();
+            }
+            animations.push((animation_desc.name.as_str(), animation));
+        }
+
+        let lights = match &desc.lights {
+            Some(lights) => Some(
+                lights
+                    .iter()
+                    .map(load_light)
+                    .collect::<PlayerErrorResult<_>>()?,
+            ),
+            None => None,
+        };
+
+        let camera = match &desc.camera {
+            Some(camera) => Some(load_camera(camera, self.camera.get())?),
+            None => None,
+        };
+
+        let environment = match &desc.environment {
+            Some(environment) => Some(self.request_environment(&environment.file).await?),
+            None => None,
+        };
+        let background = match &desc.background {
+            Some(background) => {
+                let background = Background::from(background);
+                let images = background.request_images().await?;
+                Some((background, images))
+            }
+            None => None,
+        };
+
+        // Everything is loaded, the background can still fail on images that don't fit it
+        // and goes first so that nothing else changed then
+        if let Some((background, images)) = background {
+            self.background.get_mut().set(
+                &self.device,
+                &self.queue,
+                background,
+                images,
+                &self.environment,
+            )?;
+        }
+
+        self.obj_models = models;
+        *self.scene.borrow_mut() = scene;
+        self.camera_follows_scene.set(true);
+
+        if let Some(lights) = lights {
+            self.set_lights(lights);
+        }
+
+        // Rebinds the background to it
+        if let Some(environment) = environment {
+            self.set_environment(environment);
+        }
+        if let Some(environment) = &desc.environment {
+            self.set_environment_intensity(environment.intensity.unwrap_or(1.0));
+        }
+
+        if let Some(settings) = desc.render {
+            self.set_render_settings(settings);
+        }
+
+        if let Some(camera) = camera {
+            self.set_camera(camera);
+        }
+
+        // Without a place for the camera it looks at all of the models
+        let placed = matches!(&desc.camera, Some(c) if c.eye.is_some() || c.target.is_some())
+            || self
+                .scene
+                .borrow()
+                .iter()
+                .any(|(_, n)| n.content == Some(NodeContent::Camera));
+        if !placed {
+            self.frame_all();
+        }
+
+        self.animation_clear();
+        for (label, animation) in animations {
+            self.animation_insert(label, animation);
+        }
+
+        Ok(())
+    }
+}
+
+fn add_node(scene: &mut Scene, parent: Option<NodeId>, desc: &NodeDesc) {
+    let content = desc.content.as_ref().map(NodeContent::from);
+    let transform = NodeTransform::from(&TransformDesc {
+        translation: desc.translation,
+        rotation: desc.rotation,
+        scale: desc.scale,
+    });
+    let id = match scene.add_node(parent, &desc.name, transform, content.clone()) {
+        Some(id) => id,
+        None => return,
+    };
+
+    for (i, instance) in desc.instances.iter().enumerate() {
+        let name = format!("{} {}", desc.name, i + 1);
+        scene.add_node(Some(id), &name, instance.into(), content.clone());
+    }
+
+    for child in &desc.children {
+        add_node(scene, Some(id), child);
+    }
+}
+
+fn load_light(desc: &LightDesc) -> PlayerErrorResult<Light> {
+    if desc.direction == Some([0.0; 3]) {
+        return Err(MainPlayerError::SceneError(
+            "expect a light direction with a length, got [0, 0, 0]".to_owned(),
+        ));
+    }
+
+    if matches!(desc.shadow, Some(s) if s.resolution == 0) {
+        return Err(MainPlayerError::SceneError(
+            "expect a shadow resolution of at least 1 texel, got 0".to_owned(),
+        ));
+    }
+
+    let light = Light::from(desc);
+    if let (LightKind::Spot, Some(shadow)) = (light.kind, light.shadow) {
+        if light.outer_cone <= 0.0 {
+            return Err(MainPlayerError::SceneError(format!(
+                "expect a spot light casting shadows to have an outer_cone above 0, got {}",
+                light.outer_cone.to_degrees()
+            )));
+        }
+        let far = light.range.unwrap_or(shadow.extent);
+        if far <= SPOT_SHADOW_NEAR {
+            return Err(MainPlayerError::SceneError(format!(
+                "expect a spot light casting shadows to reach past {}, got {}",
+                SPOT_SHADOW_NEAR, far
+            )));
+        }
+    }
+
+    Ok(light)
+}
+
+/// `camera` with what `desc` changes, projections without a volume are rejected.
+fn load_camera(desc: &CameraDesc, mut camera: Camera) -> PlayerErrorResult<Camera> {
+    if let Some(eye) = desc.eye {
+        camera.eye = eye.into();
+    }
+    if let Some(target) = desc.target {
+        camera.target = target.into();
+    }
+    if let Some(up) = desc.up {
+        camera.up = up.into();
+    }
+    camera.fovy = desc.fovy.unwrap_or(camera.fovy);
+    camera.znear = desc.znear.unwrap_or(camera.znear);
+    camera.zfar = desc.zfar.unwrap_or(camera.zfar);
+    camera.projection = desc.projection.unwrap_or(camera.projection);
+
+    if camera.fovy <= 0.0 || camera.fovy >= 180.0 {
+        return Err(MainPlayerError::SceneError(format!(
+            "expect a camera fovy between 0 and 180 degrees, got {}",
+            camera.fovy
+        )));
+    }
+    if camera.znear <= 0.0 || camera.zfar <= camera.znear {
+        return Err(MainPlayerError::SceneError(format!(
+            "expect 0 < znear < zfar, got znear {} and zfar {}",
+            camera.znear, camera.zfar
+        )));
+    }
+
+    Ok(camera)
+}
+
+fn load_track(desc: &TrackDesc) -> PlayerErrorResult<Track> {
+    if desc.times.is_empty() || !desc.times.windows(2).all(|t| t[0] < t[1]) {
+        return Err(MainPlayerError::SceneError(format!(
+            "expect non-empty, strictly ascending keyframe times, got {:?}",
+            desc.times
+        )));
+    }
+
+    // Cubic splines carry an in and an out tangent around every value
+    let expected = match desc.interpolation {
+        Interpolation::CubicSpline => desc.times.len() * 3,
+        _ => desc.times.len(),
+    };
+    if desc.values.len() != expected {
+        return Err(MainPlayerError::SceneError(format!(
+            "expect {} keyframe values for {} times, got {}",
+            expected,
+            desc.times.len(),
+            desc.values.len()
+        )));
+    }
+
+    let vectors = || -> PlayerErrorResult<_> {
+        let values = desc
+            .values
+            .iter()
+            .map(|v| match v[..] {
+                [x, y, z] => Ok(cgmath::Vector3::new(x, y, z)),
+                _ => Err(MainPlayerError::SceneError(format!(
+                    "expect [x, y, z] keyframes, got {:?}",
+                    v
+                ))),
+            })
+            .collect::<PlayerErrorResult<_>>()?;
+
+        Ok(Keyframes {
+            times: desc.times.clone(),
+            values,
+            interpolation: desc.interpolation,
+            easing: desc.easing,
+        })
+    };
+
+    Ok(match &desc.target {
+        TargetDesc::LightPosition => Track::LightPosition {
+            light: desc.light,
+            keyframes: vectors()?,
+        },
+        TargetDesc::LightColor => Track::LightColor {
+            light: desc.light,
+            keyframes: vectors()?,
+        },
+        TargetDesc::CameraEye => Track::CameraEye(vectors()?),
+        TargetDesc::CameraTarget => Track::CameraTarget(vectors()?),
+        TargetDesc::Node { node, property } => Track::SceneNode {
+            node: node.clone(),
+            track: match property {
+                PropertyDesc::Translation => NodeTrack::Translation(vectors()?),
+                PropertyDesc::Scale => NodeTrack::Scale(vectors()?),
+                PropertyDesc::Rotation => {
+                    let values = desc
+                        .values
+                        .iter()
+                        .map(|v| match v[..] {
+                            [x, y, z, w] => Ok(cgmath::Quaternion::new(w, x, y, z)),
+                            _ => Err(MainPlayerError::SceneError(format!(
+                                "expect [x, y, z, w] keyframes, got {:?}",
+                                v
+                            ))),
+                        })
+                        .collect::<PlayerErrorResult<_>>()?;
+
+                    NodeTrack::Rotation(Keyframes {
+                        times: desc.times.clone(),
+                        values,
+                        interpolation: desc.interpolation,
+                        easing: desc.easing,
+                    })
+                }
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::main_player::resources::shadow;
+
+    fn track(json: &str) -> PlayerErrorResult<Track> {
+        load_track(&serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn parse_the_main_scene() {
+        let desc: SceneDesc =
+            serde_json::from_str(include_str!("../../../static/scene/main.json")).unwrap();
+
+        assert_eq!(desc.models["yueqin"], "Yueqin.obj");
+        assert_eq!(desc.nodes.len(), 2);
+        assert_eq!(desc.nodes[1].children[0].name, "light");
+        assert!(matches!(desc.background, Some(BackgroundDesc::Color(_))));
+
+        let clip = &desc.animations[0];
+        assert_eq!(clip.loop_mode, LoopMode::Repeat);
+        assert!(clip.autoplay);
+        match &clip.source {
+            ClipSource::Tracks(tracks) => {
+                for t in tracks {
+                    load_track(t).unwrap();
+                }
+            }
+            ClipSource::ModelClip { .. } => panic!("expect tracks"),
+        }
+    }
+
+    #[test]
+    fn parse_an_empty_scene() {
+        let desc: SceneDesc = serde_json::from_str("{}").unwrap();
+
+        assert!(desc.models.is_empty() && desc.nodes.is_empty() && desc.animations.is_empty());
+        assert!(desc.lights.is_none() && desc.camera.is_none() && desc.render.is_none());
+    }
+
+    #[test]
+    fn reject_lights_without_direction() {
+        let light = |json: &str| load_light(&serde_json::from_str(json).unwrap());
+
+        let spot = light(r#"{ "kind": "spot", "direction": [0.0, -2.0, 0.0] }"#).unwrap();
+        assert_eq!(spot.axis(), cgmath::Vector3::new(0.0, -1.0, 0.0));
+        assert!(light(r#"{ "kind": "point" }"#).is_ok());
+        assert!(light(r#"{ "kind": "directional", "direction": [0.0, 0.0, 0.0] }"#).is_err());
+
+        // Set by hand it falls back instead of becoming NaN
+        let zero = Light {
+            direction: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            ..spot
+        };
+        assert_eq!(zero.axis(), Light::default().direction);
+    }
+
+    #[test]
+    fn reject_spot_shadows_without_volume() {
+        let light = |json: &str| load_light(&serde_json::from_str(json).unwrap());
+
+        assert!(light(r#"{ "kind": "spot", "outer_cone": 20.0, "shadow": {} }"#).is_ok());
+        // Without shadow nothing is projected
+        assert!(light(r#"{ "kind": "spot", "outer_cone": 0.0 }"#).is_ok());
+
+        let invalid = [
+            r#"{ "kind": "spot", "outer_cone": 0.0, "shadow": {} }"#,
+            r#"{ "kind": "spot", "range": 0.05, "shadow": {} }"#,
+            r#"{ "kind": "spot", "shadow": { "extent": 0.0 } }"#,
+            r#"{ "kind": "spot", "shadow": { "resolution": 0 } }"#,
+            r#"{ "kind": "directional", "shadow": { "resolution": 0 } }"#,
+        ];
+        for json in invalid {
+            assert!(
+                matches!(light(json), Err(MainPlayerError::SceneError(_))),
+                "{}",
+                json
+            );
+        }
+
+        // Set by hand they cast no shadow instead of panicking
+        let spot = Light {
+            kind: LightKind::Spot,
+            shadow: Some(ShadowSettings::default()),
+            ..Light::default()
+        };
+        assert!(spot.shadow_view_proj().is_some());
+        let narrow = Light {
+            outer_cone: 0.0,
+            ..spot
+        };
+        assert!(narrow.shadow_view_proj().is_none());
+        let short = Light {
+            range: Some(0.0),
+            ..spot
+        };
+        assert!(short.shadow_view_proj().is_none());
+        let empty = Light {
+            shadow: Some(ShadowSettings {
+                resolution: 0,
+                ..ShadowSettings::default()
+            }),
+            ..spot
+        };
+        let caster = shadow::casters(&[empty]).next().unwrap();
+        assert_eq!(caster.settings.resolution, 1);
+    }
+
+    #[test]
+    fn reject_cameras_without_volume() {
+        let camera = |json: &str| {
+            let camera = Camera {
+                eye: cgmath::Point3::new(0.0, 0.0, 10.0),
+                target: cgmath::Point3::new(0.0, 0.0, 0.0),
+                up: cgmath::Vector3::unit_y(),
+                aspect: 1.0,
+                fovy: 45.0,
+                znear: 0.1,
+                zfar: 100.0,
+                projection: Projection::Perspective,
+            };
+            load_camera(&serde_json::from_str(json).unwrap(), camera)
+        };
+
+        let wide = camera(r#"{ "fovy": 120.0, "znear": 0.5, "zfar": 50.0 }"#).unwrap();
+        assert_eq!((wide.fovy, wide.znear, wide.zfar), (120.0, 0.5, 50.0));
+
+        let invalid = [
+            r#"{ "fovy": 0.0 }"#,
+            r#"{ "fovy": -10.0 }"#,
+            r#"{ "fovy": 180.0 }"#,
+            r#"{ "znear": 0.0 }"#,
+            r#"{ "znear": -1.0 }"#,
+            r#"{ "znear": 1.0, "zfar": 1.0 }"#,
+            r#"{ "znear": 2.0, "zfar": 1.0 }"#,
+        ];
+        for json in invalid {
+            assert!(
+                matches!(camera(json), Err(MainPlayerError::SceneError(_))),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        assert!(serde_json::from_str::<SceneDesc>(r#"{ "model": {} }"#).is_err());
+        assert!(serde_json::from_str::<SceneDesc>(r#"{ "camera": { "fov": 45 } }"#).is_err());
+        assert!(serde_json::from_str::<SceneDesc>(
+            r#"{ "nodes": [{ "name": "box", "tranlsation": [1.0, 0.0, 0.0] }] }"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<SceneDesc>(
+            r#"{ "nodes": [{ "name": "box", "instances": [{ "sacle": [2.0, 2.0, 2.0] }] }] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn nodes_with_instances_and_children() {
+        let desc: SceneDesc = serde_json::from_str(
+            r#"{ "nodes": [{
+                "name": "row",
+                "translation": [1.0, 0.0, 0.0],
+                "content": { "model": "box" },
+                "instances": [{ "translation": [0.0, 2.0, 0.0] }],
+                "children": [{ "name": "eye", "content": "camera" }]
+            }] }"#,
+        )
+        .unwrap();
+
+        let mut scene = Scene::default();
+        add_node(&mut scene, None, &desc.nodes[0]);
+
+        let row = scene.find("row").unwrap();
+        let instance = scene.find("row 1").unwrap();
+        let eye = scene.find("eye").unwrap();
+        assert_eq!(scene.node(instance).unwrap().parent(), Some(row));
+        assert_eq!(scene.node(eye).unwrap().parent(), Some(row));
+        assert_eq!(
+            scene.node(instance).unwrap().content,
+            Some(NodeContent::Model("box".to_owned()))
+        );
+        assert_eq!(scene.node(eye).unwrap().content, Some(NodeContent::Camera));
+
+        let transform = &scene.node(row).unwrap().transform;
+        assert_eq!(transform.translation, cgmath::Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(transform.scale, NodeTransform::default().scale);
+    }
+
+    #[test]
+    fn lights_and_backgrounds() {
+        let desc: SceneDesc = serde_json::from_str(
+            r#"{
+                "lights": [{ "kind": "spot", "outer_cone": 90.0 }],
+                "background": { "gradient": {
+                    "top": [0.0, 0.0, 1.0, 1.0],
+                    "bottom": [1.0, 1.0, 1.0, 1.0]
+                } }
+            }"#,
+        )
+        .unwrap();
+
+        let light = Light::from(&desc.lights.unwrap()[0]);
+        assert_eq!(light.kind, LightKind::Spot);
+        assert!((light.outer_cone - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(light.intensity, Light::default().intensity);
+
+        match Background::from(desc.background.as_ref().unwrap()) {
+            Background::Gradient { top, .. } => assert_eq!(top.b, 1.0),
+            background => panic!("expect a gradient, got {:?}", background),
+        }
+    }
+
+    #[test]
+    fn load_tracks() {
+        let rotation = track(
+            r#"{
+                "target": { "node": { "node": "pivot", "property": "rotation" } },
+                "times": [0.0, 1.0],
+                "values": [[0.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 0.0]]
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            rotation,
+            Track::SceneNode {
+                track: NodeTrack::Rotation(_),
+                ..
+            }
+        ));
+
+        let cubic = track(
+            r#"{
+                "target": "camera_eye",
+                "times": [0.0, 1.0],
+                "values": [[0, 0, 0], [0, 0, 0], [0, 0, 0], [0, 0, 0], [1, 0, 0], [0, 0, 0]],
+                "interpolation": "cubic_spline"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(cubic.duration(), 1.0);
+    }
+
+    #[test]
+    fn reject_invalid_tracks() {
+        let invalid = [
+            // No keyframes
+            r#"{ "target": "camera_eye", "times": [], "values": [] }"#,
+            // Times not ascending
+            r#"{ "target": "camera_eye", "times": [1.0, 0.0], "values": [[0, 0, 0], [1, 0, 0]] }"#,
+            r#"{ "target": "camera_eye", "times": [0.0, 0.0], "values": [[0, 0, 0], [1, 0, 0]] }"#,
+            // Missing values
+            r#"{ "target": "camera_eye", "times": [0.0, 1.0], "values": [[0, 0, 0]] }"#,
+            // Cubic splines without tangents
+            r#"{
+                "target": "camera_eye",
+                "times": [0.0, 1.0],
+                "values": [[0, 0, 0], [1, 0, 0]],
+                "interpolation": "cubic_spline"
+            }"#,
+            // Rotations are quaternions
+            r#"{
+                "target": { "node": { "node": "pivot", "property": "rotation" } },
+                "times": [0.0],
+                "values": [[0.0, 0.0, 0.0]]
+            }"#,
+        ];
+
+        for json in invalid {
+            assert!(
+                matches!(track(json), Err(MainPlayerError::SceneError(_))),
+                "{}",
+                json
+            );
+        }
+    }
+}
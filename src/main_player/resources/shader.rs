@@ -1,17 +1,46 @@
 use crate::main_player::error::PlayerErrorResult;
 
+/// How a render pipeline writes the color and depth of what it draws.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PipelineOptions {
+    /// `None` replaces the color behind
+    pub(crate) blend: Option<wgpu::BlendState>,
+    pub(crate) depth_write: bool,
+    /// Samples per pixel of the targets drawn into
+    pub(crate) sample_count: u32,
+}
+
+impl Default for PipelineOptions {
+    /// Opaque, replacing the color and depth behind it.
+    fn default() -> Self {
+        Self {
+            blend: None,
+            depth_write: true,
+            sample_count: 1,
+        }
+    }
+}
+
+impl PipelineOptions {
+    /// Blended over what is behind by its alpha, tested against the depth but leaving it alone
+    /// so transparent meshes drawn back to front all show.
+    pub(crate) fn transparent() -> Self {
+        Self {
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+            depth_write: false,
+            ..Default::default()
+        }
+    }
+}
+
 pub(crate) struct Shader {
     lable: String,
     code: String,
 }
 
 impl Shader {
-    pub(crate) async fn from_file_name<'a>(
-        lable: &'a str,
-        filename: &str,
-    ) -> PlayerErrorResult<Shader> {
-        let code = crate::requests::request_string(&format!("static/shader/{}", filename))
-            .await?;
+    pub(crate) async fn from_file_name(lable: &str, filename: &str) -> PlayerErrorResult<Shader> {
+        let code = crate::requests::request_string(&format!("static/shader/{}", filename)).await?;
 
         Ok(Self {
             lable: lable.to_string(),
@@ -26,6 +55,7 @@ impl Shader {
         color_format: wgpu::TextureFormat,
         depth_format: Option<wgpu::TextureFormat>,
         vertex_layouts: &[wgpu::VertexBufferLayout],
+        options: PipelineOptions,
     ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some(&self.lable),
@@ -45,10 +75,7 @@ impl Shader {
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: color_format,
-                    blend: Some(wgpu::BlendState {
-                        alpha: wgpu::BlendComponent::REPLACE,
-                        color: wgpu::BlendComponent::REPLACE,
-                    }),
+                    blend: options.blend,
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -66,11 +93,67 @@ impl Shader {
             },
             depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
                 format,
-                depth_write_enabled: true,
+                depth_write_enabled: options.depth_write,
                 depth_compare: wgpu::CompareFunction::Less,
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
+            multisample: wgpu::MultisampleState {
+                count: options.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    }
+
+    /// Pipeline writing nothing but depth, with a slope scaled bias against shadow acne.
+    pub(crate) fn create_depth_pipeline(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        depth_format: wgpu::TextureFormat,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.lable),
+            source: wgpu::ShaderSource::Wgsl(self.code.clone().into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Depth Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: vertex_layouts,
+            },
+            // WebGL can't link programs without fragment shader
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_format,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
             multisample: wgpu::MultisampleState {
                 count: 1,
                 mask: !0,
@@ -79,4 +162,53 @@ impl Shader {
             multiview: None,
         })
     }
+
+    /// Pipeline drawing one triangle over the whole target with `vs_main` and no vertex buffers,
+    /// it never tests the depth of passes with `depth_format`.
+    pub(crate) fn create_fullscreen_pipeline(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        fragment_entry: &str,
+        color_format: wgpu::TextureFormat,
+        depth_format: Option<wgpu::TextureFormat>,
+        options: PipelineOptions,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&self.lable),
+            source: wgpu::ShaderSource::Wgsl(self.code.clone().into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Fullscreen Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: fragment_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: options.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: options.depth_write,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: options.sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
 }
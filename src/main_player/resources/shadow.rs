@@ -0,0 +1,239 @@
+use std::ops::Range;
+
+use wgpu::util::DeviceExt;
+
+use crate::main_player::{error::PlayerErrorResult, wgpu_state::Vertex};
+
+use super::{
+    camera::CameraUniform,
+    instance,
+    light::{Light, ShadowSettings, MAX_LIGHTS},
+    model::{draw_trait::DrawModel, vertex::ModelVertex, Model},
+    shader, texture,
+};
+
+/// Most lights casting shadows at once, one layer of the shadow maps each
+pub const MAX_SHADOWS: usize = 4;
+
+/// Light casting its shadow into a layer of the shadow maps.
+pub(crate) struct Caster {
+    /// Index of the light
+    pub(crate) light: usize,
+    pub(crate) layer: usize,
+    pub(crate) view_proj: cgmath::Matrix4<f32>,
+    pub(crate) settings: ShadowSettings,
+}
+
+/// Lights casting shadows, the first [`MAX_SHADOWS`] of them get a layer.
+/// Their resolution is at least 1, an empty viewport is invalid.
+pub(crate) fn casters(lights: &[Light]) -> impl Iterator<Item = Caster> + '_ {
+    lights
+        .iter()
+        .take(MAX_LIGHTS)
+        .enumerate()
+        .filter_map(|(i, light)| Some((i, light.shadow_view_proj()?, light.shadow?)))
+        .take(MAX_SHADOWS)
+        .enumerate()
+        .map(|(layer, (light, view_proj, settings))| Caster {
+            light,
+            layer,
+            view_proj,
+            settings: ShadowSettings {
+                resolution: settings.resolution.max(1),
+                ..settings
+            },
+        })
+}
+
+/// Depth of the scene as seen by every light casting shadows,
+/// one texture array with a layer per light.
+pub(crate) struct ShadowMaps {
+    /// Kept alive for the views
+    _texture: wgpu::Texture,
+    /// All layers, sampled by the lit shaders
+    pub(crate) view: wgpu::TextureView,
+    /// One layer each, rendered by the shadow pass
+    layer_views: Vec<wgpu::TextureView>,
+    pub(crate) sampler: wgpu::Sampler,
+    /// Width and height of a layer, lights with a smaller resolution use its top left corner
+    size: u32,
+    /// View projection of every layer, bound like a camera
+    cameras: Vec<(wgpu::Buffer, wgpu::BindGroup)>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMaps {
+    pub(crate) async fn new(
+        device: &wgpu::Device,
+        camera_layout: &wgpu::BindGroupLayout,
+        skin_layout: &wgpu::BindGroupLayout,
+    ) -> PlayerErrorResult<Self> {
+        // Grows once a light casts shadows
+        let size = 1;
+        let (texture, view, layer_views) = Self::create_texture(device, size);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            // Linear filtering compares the 4 nearest texels
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let cameras = (0..MAX_SHADOWS)
+            .map(|layer| {
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("Shadow Camera Buffer {}", layer)),
+                    contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: camera_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("shadow_camera_bind_group"),
+                });
+                (buffer, bind_group)
+            })
+            .collect();
+
+        let pipeline = {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[camera_layout, skin_layout],
+                push_constant_ranges: &[],
+            });
+
+            shader::Shader::from_file_name("Shadow Shader", "shadow.wgsl")
+                .await?
+                .create_depth_pipeline(
+                    device,
+                    &layout,
+                    texture::Texture::DEPTH_FORMAT,
+                    &[ModelVertex::desc(), instance::InstanceRaw::desc()],
+                )
+        };
+
+        Ok(Self {
+            _texture: texture,
+            view,
+            layer_views,
+            sampler,
+            size,
+            cameras,
+            pipeline,
+        })
+    }
+
+    fn create_texture(
+        device: &wgpu::Device,
+        size: u32,
+    ) -> (wgpu::Texture, wgpu::TextureView, Vec<wgpu::TextureView>) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Maps"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: MAX_SHADOWS as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: texture::Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let layer_views = (0..MAX_SHADOWS as u32)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        (texture, view, layer_views)
+    }
+
+    pub(crate) fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Grow the layers to the largest resolution of the casting lights,
+    /// returns whether the texture was replaced.
+    pub(crate) fn fit(&mut self, device: &wgpu::Device, lights: &[Light]) -> bool {
+        let size = casters(lights)
+            .map(|c| c.settings.resolution)
+            .max()
+            .unwrap_or(1)
+            .min(device.limits().max_texture_dimension_2d);
+        if size <= self.size {
+            return false;
+        }
+
+        let (texture, view, layer_views) = Self::create_texture(device, size);
+        self._texture = texture;
+        self.view = view;
+        self.layer_views = layer_views;
+        self.size = size;
+
+        true
+    }
+
+    /// Draw the depth of `models` into the layer of every casting light.
+    pub(crate) fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        lights: &[Light],
+        models: &[(&Model, Range<u32>)],
+        instance_buffer: &wgpu::Buffer,
+    ) {
+        for caster in casters(lights) {
+            let (camera_buffer, camera_bind_group) = &self.cameras[caster.layer];
+            queue.write_buffer(
+                camera_buffer,
+                0,
+                bytemuck::cast_slice(&[CameraUniform::from_view_proj(caster.view_proj)]),
+            );
+
+            let mut shadow_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.layer_views[caster.layer],
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+
+            let resolution = caster.settings.resolution.min(self.size) as f32;
+            shadow_pass.set_viewport(0.0, 0.0, resolution, resolution, 0.0, 1.0);
+            shadow_pass.set_pipeline(&self.pipeline);
+            shadow_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            for (model, instances) in models {
+                shadow_pass.draw_model_shadow_instanced(
+                    model,
+                    instances.clone(),
+                    camera_bind_group,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Only the player in the browser drives frames, the timing is also tested natively
+#![cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+
+use std::cell::{Cell, RefCell};
+
+use gloo::render::AnimationFrame;
+
+/// Longest step animations take at once, the page may have been in the background
+const MAX_DELTA: f32 = 0.1;
+
+/// Timing of the drawn frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// Frames drawn since the player was attached
+    pub frames: u64,
+    /// Seconds between the last two frames, 0 for the first frame after being idle
+    pub delta: f32,
+    /// Frames per second, smoothed over the recent frames
+    pub fps: f32,
+    /// Milliseconds the last frame took to update and encode on the CPU
+    pub cpu_time: f32,
+}
+
+impl FrameStats {
+    fn record(&mut self, delta: f32, cpu_time: f32) {
+        self.frames += 1;
+        self.delta = delta;
+        self.cpu_time = cpu_time;
+
+        if delta > 0.0 {
+            self.fps = if self.fps > 0.0 {
+                self.fps * 0.9 + 0.1 / delta
+            } else {
+                1.0 / delta
+            };
+        }
+    }
+}
+
+/// Draws at most once per display refresh and only while somebody asks for frames,
+/// browsers also stop it for pages in the background.
+#[derive(Default)]
+pub(super) struct FrameScheduler {
+    pending: RefCell<Option<AnimationFrame>>,
+    /// The handle of the running frame can't be dropped inside its own callback,
+    /// it is kept until the next frame
+    retired: RefCell<Option<AnimationFrame>>,
+    /// `requestAnimationFrame` timestamp of the previous frame, in milliseconds
+    last_timestamp: Cell<Option<f64>>,
+    stats: Cell<FrameStats>,
+}
+
+impl FrameScheduler {
+    /// Run `frame` on the next display refresh,
+    /// requests before that are coalesced into the one already pending.
+    pub(super) fn request(&self, frame: impl FnOnce(f64) + 'static) {
+        let mut pending = self.pending.borrow_mut();
+        if pending.is_none() {
+            *pending = Some(gloo::render::request_animation_frame(frame));
+        }
+    }
+
+    /// Start of a frame, returns the seconds passed since the previous one.
+    pub(super) fn begin(&self, timestamp: f64) -> f32 {
+        *self.retired.borrow_mut() = self.pending.borrow_mut().take();
+
+        let delta = self
+            .last_timestamp
+            .replace(Some(timestamp))
+            .map_or(0.0, |last| ((timestamp - last) / 1000.0) as f32);
+
+        delta.clamp(0.0, MAX_DELTA)
+    }
+
+    /// End of a frame, `keep_running` if the next frame follows right away.
+    pub(super) fn end(&self, delta: f32, cpu_time: f32, keep_running: bool) {
+        let mut stats = self.stats.get();
+        stats.record(delta, cpu_time);
+        self.stats.set(stats);
+
+        // Time spent idle is not animated
+        if !keep_running {
+            self.last_timestamp.set(None);
+        }
+    }
+
+    pub(super) fn stats(&self) -> FrameStats {
+        self.stats.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_smooth_fps() {
+        let mut stats = FrameStats::default();
+        stats.record(0.0, 1.0);
+        assert_eq!(stats.frames, 1);
+        assert_eq!(stats.fps, 0.0);
+
+        stats.record(0.02, 2.0);
+        assert_eq!(stats.fps, 50.0);
+        assert_eq!(stats.cpu_time, 2.0);
+
+        stats.record(0.01, 2.0);
+        assert!((stats.fps - 55.0).abs() < 1e-3);
+        assert_eq!(stats.frames, 3);
+        assert_eq!(stats.delta, 0.01);
+    }
+
+    #[test]
+    fn delta_between_frames() {
+        let scheduler = FrameScheduler::default();
+        assert_eq!(scheduler.begin(1000.0), 0.0);
+        scheduler.end(0.0, 1.0, true);
+
+        assert!((scheduler.begin(1016.0) - 0.016).abs() < 1e-6);
+        scheduler.end(0.016, 1.0, true);
+
+        // A page in the background doesn't jump the animations
+        assert_eq!(scheduler.begin(5000.0), MAX_DELTA);
+        scheduler.end(MAX_DELTA, 1.0, true);
+        assert_eq!(scheduler.stats().frames, 3);
+    }
+
+    #[test]
+    fn idle_time_is_not_animated() {
+        let scheduler = FrameScheduler::default();
+        scheduler.begin(1000.0);
+        scheduler.end(0.0, 1.0, false);
+
+        assert_eq!(scheduler.begin(1050.0), 0.0);
+    }
+}
@@ -1,75 +1,136 @@
-use cgmath::{InnerSpace, Rotation3, Zero};
-use once_cell::sync::OnceCell;
+use cgmath::{MetricSpace, Transform};
+use image::RgbaImage;
 use std::{
-    cell::{Cell, RefCell},
+    cell::{Cell, RefCell, RefMut},
     collections::HashMap,
-    f32::consts::PI,
+    ops::Range,
 };
+#[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
 use wgpu::util::DeviceExt;
 
 use super::{
     error::{MainPlayerError, PlayerErrorResult},
-    resources::{camera, instance, light, model, shader, texture},
+    render_target::RenderTarget,
+    resources::{
+        animation::{AnimationClip, AnimationPlayer},
+        background::{Background, BackgroundPass},
+        bounds::{Aabb, BoundingSphere},
+        camera, environment, instance, light, model,
+        pipelines::ScenePipelines,
+        post::{self, PostChain, RenderSettings},
+        scene::{self, NodeContent, NodeId, Scene},
+        shadow, texture,
+    },
 };
 
-static mut STATE: OnceCell<State> = OnceCell::new();
-
-pub(super) struct State {
-    pub surface: wgpu::Surface,
+pub struct State {
+    pub(crate) target: RefCell<RenderTarget>,
     pub config: RefCell<wgpu::SurfaceConfiguration>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
-    pub obj_models: HashMap<String, model::Model>,
+    pub(crate) model_layouts: model::ModelLayouts,
+    pub(crate) obj_models: HashMap<String, model::Model>,
+    pub(crate) light_model: model::Model,
+    pub(crate) animations: RefCell<HashMap<String, AnimationPlayer>>,
 
-    pub light_uniform: Cell<light::LightUniform>,
+    /// Uploaded to `light_buffer` whenever they change
+    pub(crate) lights: RefCell<Vec<light::Light>>,
     pub light_buffer: wgpu::Buffer,
-    pub light_bind_group: wgpu::BindGroup,
+    pub(crate) light_bind_group_layout: wgpu::BindGroupLayout,
+    /// Rebuilt when the shadow maps grow or the environment changes
+    pub light_bind_group: RefCell<wgpu::BindGroup>,
+    pub(crate) shadow_maps: RefCell<shadow::ShadowMaps>,
+    /// Ambient light of the lit shaders, black until one is loaded
+    pub(crate) environment: environment::Environment,
+    /// Scale of the ambient light of the environment
+    pub(crate) environment_intensity: Cell<f32>,
 
     pub camera: Cell<camera::Camera>,
+    /// Follows the camera set from elsewhere, moves it with the input of the viewer
+    pub(crate) camera_controller: RefCell<camera::CameraController>,
+    /// Camera nodes and camera tracks place the camera, until the input of the viewer moves it
+    pub(crate) camera_follows_scene: Cell<bool>,
     pub camera_uniform: Cell<camera::CameraUniform>,
     pub camera_buffer: wgpu::Buffer,
     pub camera_bind_group: wgpu::BindGroup,
 
-    pub instances: Vec<instance::Instance>,
-    pub instance_buffer: wgpu::Buffer,
+    pub scene: RefCell<Scene>,
+    /// Drawn behind the models
+    pub(crate) background: RefCell<BackgroundPass>,
+    /// HDR frame the scene is drawn into and the effects on it
+    pub(crate) post: RefCell<PostChain>,
+    /// Grows with the nodes of the scene, never shrinks
+    pub(crate) instance_buffer: RefCell<wgpu::Buffer>,
 
-    pub light_render_pipeline: wgpu::RenderPipeline,
-    pub yueqin_render_pipeline: wgpu::RenderPipeline,
+    pub(crate) pipelines: RefCell<ScenePipelines>,
+    /// Sample counts the adapter can draw the scene with, see [`RenderSettings::msaa`]
+    sample_counts: Vec<u32>,
+    /// Warned that multisampling turns ambient occlusion off
+    ssao_warned: Cell<bool>,
 
-    pub depth_texture: texture::Texture,
+    pub(crate) depth_texture: RefCell<texture::Texture>,
 
     pub height: Cell<u32>,
     pub width: Cell<u32>,
+}
 
-    animation: OnceCell<(
-        RefCell<HashMap<String, Box<dyn Fn(&State)>>>,
-        gloo::timers::callback::Interval,
-    )>,
+/// To the console of the browser, or to stderr off the web.
+fn warn(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    gloo::console::warn!(message);
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{}", message);
 }
 
+/// Range in the instance buffer of every model.
+type InstanceRanges = Vec<(String, Range<u32>)>;
+
 impl State {
-    pub fn get<'a>() -> PlayerErrorResult<&'a Self> {
-        if let Some(state) = unsafe { STATE.get() } {
-            Ok(state)
-        } else {
-            Err(MainPlayerError::StateNotInitError)
-        }
-    }
+    /// Build a state drawing into the canvas.
+    ///
+    /// No model is loaded, add them with [`State::load_model`].
+    #[cfg(target_arch = "wasm32")]
+    pub async fn from_canvas(canvas: &HtmlCanvasElement) -> PlayerErrorResult<Self> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let surface = instance.create_surface_from_canvas(canvas);
 
-    pub async fn get_or_init<'a>(canvas: &HtmlCanvasElement) -> PlayerErrorResult<&'a State> {
-        let (width, height) = (canvas.width(), canvas.height());
+        Self::new(&instance, Some(surface), canvas.width(), canvas.height()).await
+    }
 
+    /// Build a state drawing into an offscreen texture instead of a canvas,
+    /// read the frames back with [`State::read_pixels`].
+    ///
+    /// No model is loaded, add them with [`State::load_model`].
+    pub async fn new_headless(width: u32, height: u32) -> PlayerErrorResult<Self> {
         let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = instance.create_surface_from_canvas(&canvas);
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .expect("unexpected error cause adapter not available");
+
+        Self::new(&instance, None, width, height).await
+    }
+
+    async fn new(
+        instance: &wgpu::Instance,
+        surface: Option<wgpu::Surface>,
+        width: u32,
+        height: u32,
+    ) -> PlayerErrorResult<Self> {
+        let mut adapter_options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: surface.as_ref(),
+            force_fallback_adapter: false,
+        };
+        let adapter = match instance.request_adapter(&adapter_options).await {
+            Some(adapter) => adapter,
+            // Headless machines may only have a software adapter like llvmpipe
+            None if surface.is_none() => {
+                adapter_options.force_fallback_adapter = true;
+                instance
+                    .request_adapter(&adapter_options)
+                    .await
+                    .ok_or(MainPlayerError::AdapterNotFoundError)?
+            }
+            None => return Err(MainPlayerError::AdapterNotFoundError),
+        };
 
         let (device, queue) = adapter
             .request_device(
@@ -78,7 +139,7 @@ impl State {
                     limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
-                        wgpu::Limits::default()
+                        wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
                     },
                     label: None,
                 },
@@ -88,69 +149,29 @@ impl State {
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
+            format: match &surface {
+                Some(surface) => surface.get_supported_formats(&adapter)[0],
+                None => RenderTarget::OFFSCREEN_FORMAT,
+            },
             width,
             height,
             present_mode: wgpu::PresentMode::Fifo,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
-        surface.configure(&device, &config);
-
-        //==Model==
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
 
-        let mut obj_models = HashMap::new();
+        let target = match surface {
+            Some(surface) => {
+                surface.configure(&device, &config);
+                RenderTarget::Surface(surface)
+            }
+            None => RenderTarget::offscreen(&device, &config),
+        };
 
-        let yueqin_obj_model =
-            model::Model::from_file_name("Yueqin.obj", &device, &queue, &texture_bind_group_layout)
-                .await
-                .unwrap();
-        obj_models.insert("yueqin".to_owned(), yueqin_obj_model);
+        //==Model==
+        let model_layouts = model::ModelLayouts::new(&device);
 
-        let cube_obj_model =
-            model::Model::from_file_name("cube.obj", &device, &queue, &texture_bind_group_layout)
-                .await
-                .unwrap();
-        obj_models.insert("cube".to_owned(), cube_obj_model);
+        let light_model =
+            model::Model::from_file_name("cube.obj", &device, &queue, &model_layouts).await?;
 
         //==Camera==
         let camera = camera::Camera {
@@ -162,9 +183,10 @@ impl State {
             // which way is "up"
             up: cgmath::Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
-            fovy: 10.0,
+            fovy: 45.0,
             znear: 0.1,
             zfar: 10000.0,
+            projection: camera::Projection::Perspective,
         };
 
         let mut camera_uniform = camera::CameraUniform::new();
@@ -201,267 +223,696 @@ impl State {
         });
 
         //==Light==
-        let light_uniform = light::LightUniform {
-            position: [2.0, 2.0, 2.0],
-            _padding: 0,
-            color: [1.0, 1.0, 1.0],
-            _padding2: 0,
-        };
+        let lights = vec![light::Light {
+            position: cgmath::Point3::new(2.0, 2.0, 2.0),
+            ..Default::default()
+        }];
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Light VB"),
-            contents: bytemuck::cast_slice(&[light_uniform]),
+            contents: bytemuck::cast_slice(&[light::LightsUniform::new(&lights, 1)]),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         let light_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            sample_type: wgpu::TextureSampleType::Depth,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
+                    // Irradiance, prefiltered environment and BRDF lookup table
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::Cube,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
                 label: None,
             });
 
-        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &light_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: light_buffer.as_entire_binding(),
-            }],
-            label: None,
-        });
+        //==Shadow==
+        let shadow_maps =
+            shadow::ShadowMaps::new(&device, &camera_bind_group_layout, &model_layouts.skin)
+                .await?;
 
-        //==Instances==
-        let instances = (0..instance::NUM_INSTANCES_PER_ROW)
-            .flat_map(|z| {
-                (0..instance::NUM_INSTANCES_PER_ROW).map(move |x| {
-                    let x = instance::SPACE_BETWEEN
-                        * (x as f32 - instance::NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                    let z = instance::SPACE_BETWEEN
-                        * (z as f32 - instance::NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                    let position = cgmath::Vector3 { x, y: 0.0, z };
-
-                    let rotation = if position.is_zero() {
-                        cgmath::Quaternion::from_axis_angle(
-                            cgmath::Vector3::unit_z(),
-                            cgmath::Deg(0.0),
-                        )
-                    } else {
-                        cgmath::Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                    };
+        let environment = environment::Environment::neutral(&device);
 
-                    instance::Instance { position, rotation }
-                })
-            })
-            .collect::<Vec<_>>();
+        //==Background==
+        let background =
+            BackgroundPass::new(&device, &queue, post::HDR_FORMAT, &environment).await?;
 
-        let instance_data = instances
-            .iter()
-            .map(instance::Instance::to_raw)
-            .collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        let light_bind_group = Self::create_light_bind_group(
+            &device,
+            &light_bind_group_layout,
+            &light_buffer,
+            &shadow_maps,
+            &environment,
+        );
+
+        //==Post-processing==
+        let post = PostChain::new(&device, config.format, config.width, config.height).await?;
+
+        //==Instances==
+        let instance_buffer = Self::create_instance_buffer(&device, 1);
 
         //==z-Buffer==
         let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        //==Shader==
-        let shader = shader::Shader::from_file_name("Normal Shader", "bp.wgsl");
-
-        let yueqin_render_pipeline = {
-            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[
-                    &texture_bind_group_layout,
-                    &camera_bind_group_layout,
-                    &light_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
+            texture::Texture::create_depth_texture(&device, &config, 1, "depth_texture");
+
+        //==Pipelines==
+        let pipelines = ScenePipelines::new(
+            &device,
+            &model_layouts,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+            1,
+        )
+        .await?;
+        let sample_counts = post::supported_sample_counts(&adapter);
+
+        let (target, depth_texture) = (RefCell::new(target), RefCell::new(depth_texture));
+        let config = RefCell::new(config);
+        let lights = RefCell::new(lights);
+        let mut camera_controller = camera::CameraController::default();
+        camera_controller.look_from(&camera);
+        let (camera, camera_uniform) = (Cell::new(camera), Cell::new(camera_uniform));
+        let (width, height) = (Cell::new(width), Cell::new(height));
 
-            shader.await?.create_render_pipeline(
-                &device,
-                &layout,
-                config.format,
-                Some(texture::Texture::DEPTH_FORMAT),
-                &[
-                    model::vertex::ModelVertex::desc(),
-                    instance::InstanceRaw::desc(),
-                ],
-            )
-        };
+        Ok(Self {
+            target,
+            config,
+            device,
+            queue,
+            model_layouts,
+
+            camera,
+            camera_controller: RefCell::new(camera_controller),
+            camera_follows_scene: Cell::new(true),
+            camera_uniform,
+            camera_buffer,
+            camera_bind_group,
+
+            depth_texture,
+
+            obj_models: HashMap::new(),
+            light_model,
+            animations: RefCell::new(HashMap::new()),
+
+            lights,
+            light_buffer,
+            light_bind_group_layout,
+            light_bind_group: RefCell::new(light_bind_group),
+            shadow_maps: RefCell::new(shadow_maps),
+            environment,
+            environment_intensity: Cell::new(1.0),
+
+            scene: RefCell::new(Scene::default()),
+            background: RefCell::new(background),
+            post: RefCell::new(post),
+            instance_buffer: RefCell::new(instance_buffer),
+
+            pipelines: RefCell::new(pipelines),
+            sample_counts,
+            ssao_warned: Cell::new(false),
 
-        let light_render_pipeline = {
-            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Light Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
-                push_constant_ranges: &[],
-            });
+            height,
+            width,
+        })
+    }
 
-            shader::Shader::from_file_name("Light Shader", "pure.wgsl")
-                .await?
-                .create_render_pipeline(
-                    &device,
-                    &layout,
-                    config.format,
-                    Some(texture::Texture::DEPTH_FORMAT),
-                    &[model::vertex::ModelVertex::desc()],
-                )
+    /// Follow the new size of the canvas, or of the offscreen texture.
+    ///
+    /// Does nothing if the size didn't change.
+    pub fn resize(&self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (self.width.get(), self.height.get()) == (width, height) {
+            return;
+        }
+
+        self.width.set(width);
+        self.height.set(height);
+
+        let config = {
+            let mut config = self.config.borrow_mut();
+            config.width = width;
+            config.height = height;
+            config.clone()
         };
 
-        let config = RefCell::new(config);
-        let light_uniform = Cell::new(light_uniform);
-        let (camera, camera_uniform) = (Cell::new(camera), Cell::new(camera_uniform));
-        let (width, height) = (Cell::new(width), Cell::new(height));
+        self.target.borrow_mut().resize(&self.device, &config);
+        let mut post = self.post.borrow_mut();
+        post.resize(&self.device, width, height);
+        *self.depth_texture.borrow_mut() = texture::Texture::create_depth_texture(
+            &self.device,
+            &config,
+            post.sample_count(),
+            "depth_texture",
+        );
+        drop(post);
+
+        self.write_camera(camera::Camera {
+            aspect: width as f32 / height as f32,
+            ..self.camera.get()
+        });
+    }
 
-        Ok(unsafe {
-            STATE.get_or_init(|| Self {
-                surface,
-                config,
-                device,
-                queue,
+    /// Load a model under `key`, it is drawn wherever a scene node refers to the key.
+    pub async fn load_model(&mut self, key: &str, file_name: &str) -> PlayerErrorResult<()> {
+        let model = self.request_model(key, file_name).await?;
+        self.obj_models.insert(key.to_owned(), model);
 
-                camera,
-                camera_uniform,
-                camera_buffer,
-                camera_bind_group,
+        Ok(())
+    }
 
-                depth_texture,
+    /// Load a model for `key` without adding it yet.
+    pub(crate) async fn request_model(
+        &self,
+        key: &str,
+        file_name: &str,
+    ) -> PlayerErrorResult<model::Model> {
+        let mut model =
+            model::Model::from_file_name(file_name, &self.device, &self.queue, &self.model_layouts)
+                .await?;
+        model.animations.iter_mut().for_each(|a| a.retarget(key));
+
+        Ok(model)
+    }
 
-                obj_models,
+    /// Animations of the model loaded under `key`, empty if there is no such model.
+    pub fn model_animations(&self, key: &str) -> &[AnimationClip] {
+        self.obj_models
+            .get(key)
+            .map_or(&[], |m| m.animations.as_slice())
+    }
 
-                light_uniform,
-                light_buffer,
-                light_bind_group,
+    /// Add an animation, one with the same label is replaced.
+    pub fn animation_insert(&self, label: &str, animation: AnimationPlayer) {
+        self.animations
+            .borrow_mut()
+            .insert(label.to_owned(), animation);
+    }
 
-                instances,
-                instance_buffer,
+    pub fn animation_remove(&self, label: &str) -> Option<AnimationPlayer> {
+        self.animations.borrow_mut().remove(label)
+    }
 
-                light_render_pipeline,
-                yueqin_render_pipeline,
+    pub fn animation_clear(&self) {
+        self.animations.borrow_mut().clear();
+    }
 
-                height,
-                width,
+    /// Access an animation to play, pause or seek it.
+    pub fn animation_mut(&self, label: &str) -> Option<RefMut<'_, AnimationPlayer>> {
+        RefMut::filter_map(self.animations.borrow_mut(), |a| a.get_mut(label)).ok()
+    }
 
-                animation: OnceCell::new(),
-            })
-        })
+    /// Jump every animation to `time` seconds.
+    pub fn seek_animations(&self, time: f32) {
+        for animation in self.animations.borrow_mut().values_mut() {
+            animation.seek(time);
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animations
+            .borrow()
+            .values()
+            .any(AnimationPlayer::is_playing)
+    }
+
+    /// Move every playing animation `delta` seconds forward and write their values.
+    pub fn update_animations(&self, delta: f32) {
+        for animation in self.animations.borrow_mut().values_mut() {
+            animation.advance(delta);
+            animation.apply(self);
+        }
+
+        for obj_model in self.obj_models.values() {
+            obj_model.update_skins(&self.queue);
+        }
+    }
+
+    pub fn lights(&self) -> Vec<light::Light> {
+        self.lights.borrow().clone()
+    }
+
+    /// Replace all lights, only the first [`MAX_LIGHTS`](light::MAX_LIGHTS) shine.
+    pub fn set_lights(&self, lights: Vec<light::Light>) {
+        *self.lights.borrow_mut() = lights;
+        self.write_lights();
+    }
+
+    /// Change the light at `index`, nothing happens if there is none.
+    pub fn update_light(&self, index: usize, f: impl FnOnce(&mut light::Light)) {
+        let updated = self.lights.borrow_mut().get_mut(index).map(f).is_some();
+        if updated {
+            self.write_lights();
+        }
+    }
+
+    /// Light the scene with an equirectangular `.hdr` image in `static/image`.
+    pub async fn load_environment(&mut self, file_name: &str) -> PlayerErrorResult<()> {
+        let environment = self.request_environment(file_name).await?;
+        self.set_environment(environment);
+
+        Ok(())
     }
 
-    pub fn display_change(
+    /// Bake the environment of an `.hdr` image without lighting the scene with it yet.
+    pub(crate) async fn request_environment(
         &self,
-        width: u32,
-        height: u32,
-        cursor_to: (f32, f32),
-        wheel_to: f32,
+        file_name: &str,
+    ) -> PlayerErrorResult<environment::Environment> {
+        let hdr = crate::requests::HdrImage::from_name(file_name).await?;
+        environment::Environment::from_hdr(&self.device, &self.queue, hdr).await
+    }
+
+    /// Go back to the ambient light of every light.
+    pub fn clear_environment(&mut self) {
+        self.set_environment(environment::Environment::neutral(&self.device));
+    }
+
+    pub(crate) fn set_environment(&mut self, environment: environment::Environment) {
+        self.environment = environment;
+        self.rebuild_light_bind_group();
+        self.background
+            .get_mut()
+            .rebind(&self.device, &self.environment);
+    }
+
+    pub fn set_environment_intensity(&self, intensity: f32) {
+        self.environment_intensity.set(intensity);
+        self.write_lights();
+    }
+
+    pub fn background(&self) -> Background {
+        self.background.borrow().background().clone()
+    }
+
+    /// Draw `background` behind the models from the next frame on,
+    /// the current one stays if its images fail to load.
+    pub async fn set_background(&self, background: Background) -> PlayerErrorResult<()> {
+        let images = background.request_images().await?;
+
+        self.set_loaded_background(background, images)
+    }
+
+    /// [`State::set_background`] with the images it requested, in the order it requests them.
+    pub(crate) fn set_loaded_background(
+        &self,
+        background: Background,
+        images: Vec<crate::requests::Image>,
     ) -> PlayerErrorResult<()> {
-        self.width.set(width);
-        self.height.set(height);
+        self.background.borrow_mut().set(
+            &self.device,
+            &self.queue,
+            background,
+            images,
+            &self.environment,
+        )
+    }
 
-        gloo::console::log!("w", width, "h", height);
+    pub fn render_settings(&self) -> RenderSettings {
+        self.post.borrow().settings()
+    }
 
-        {
-            let mut config = self.config.borrow_mut();
-            config.width = width;
-            config.height = height;
+    /// Exposure, tonemapping, bloom and multisampling of the next frames,
+    /// `msaa` is lowered to the next sample count the adapter supports.
+    pub fn set_render_settings(&self, mut settings: RenderSettings) {
+        settings.msaa = self
+            .sample_counts
+            .iter()
+            .copied()
+            .filter(|&count| count <= settings.msaa)
+            .max()
+            .unwrap_or(1);
+        if settings.ssao.is_some() && settings.msaa > 1 && !self.ssao_warned.replace(true) {
+            warn("SSAO is skipped while multisampling, set msaa to 1 to see it");
         }
 
-        // camera controller
-        let camera_pos = {
-            let mut camera_pos = self.camera.get().get_pos();
+        let mut post = self.post.borrow_mut();
+        if post.sample_count() != settings.msaa {
+            post.set_sample_count(&self.device, settings.msaa);
+            *self.depth_texture.borrow_mut() = texture::Texture::create_depth_texture(
+                &self.device,
+                &self.config.borrow(),
+                settings.msaa,
+                "depth_texture",
+            );
+            self.pipelines
+                .borrow_mut()
+                .set_sample_count(&self.device, settings.msaa);
+            self.background
+                .borrow_mut()
+                .set_sample_count(&self.device, settings.msaa);
+        }
+        post.set_settings(settings);
+    }
 
-            let r = wheel_to;
+    fn write_lights(&self) {
+        let mut lights_uniform =
+            light::LightsUniform::new(&self.lights.borrow(), self.shadow_maps.borrow().size());
+        if self.environment.mip_count > 0 {
+            lights_uniform.environment_intensity = self.environment_intensity.get();
+            lights_uniform.environment_mip_count = self.environment.mip_count as f32;
+        }
 
-            let r_xy = (cursor_to.1 * PI).sin() * r;
-            camera_pos.1 = (cursor_to.1 * PI).cos() * r;
+        self.queue.write_buffer(
+            &self.light_buffer,
+            0,
+            bytemuck::cast_slice(&[lights_uniform]),
+        );
+    }
 
-            camera_pos.0 = (cursor_to.0 * PI).cos() * r_xy;
-            camera_pos.2 = (cursor_to.0 * PI).sin() * r_xy;
+    /// Bind the current shadow maps and environment, and the lights covering them.
+    fn rebuild_light_bind_group(&self) {
+        *self.light_bind_group.borrow_mut() = Self::create_light_bind_group(
+            &self.device,
+            &self.light_bind_group_layout,
+            &self.light_buffer,
+            &self.shadow_maps.borrow(),
+            &self.environment,
+        );
+        self.write_lights();
+    }
 
-            camera_pos
+    fn create_light_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        light_buffer: &wgpu::Buffer,
+        shadow_maps: &shadow::ShadowMaps,
+        environment: &environment::Environment,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&shadow_maps.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&shadow_maps.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&environment.irradiance),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&environment.prefiltered),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&environment.brdf_lut),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: wgpu::BindingResource::Sampler(&environment.sampler),
+                },
+            ],
+            label: None,
+        })
+    }
+
+    /// Controller moving the camera, what it is given applies on the next [`State::update_camera`].
+    pub fn camera_controller(&self) -> RefMut<'_, camera::CameraController> {
+        self.camera_controller.borrow_mut()
+    }
+
+    /// Move the camera with the input given to the controller and its inertia over `delta` seconds,
+    /// returns true while it keeps moving on its own.
+    pub fn update_camera(&self, delta: f32) -> bool {
+        let (camera, is_moving) = {
+            let mut controller = self.camera_controller.borrow_mut();
+            if !controller.update(delta) {
+                return controller.is_moving();
+            }
+            (controller.apply(self.camera.get()), controller.is_moving())
         };
+        self.camera_follows_scene.set(false);
+        self.write_camera(camera);
 
-        let camera = camera::Camera {
-            eye: cgmath::Point3 {
-                x: camera_pos.0,
-                y: camera_pos.1,
-                z: camera_pos.2,
-            },
-            aspect: width as f32 / height as f32,
-            ..self.camera.get()
+        is_moving
+    }
+
+    /// Look through `camera`, the controller continues from it.
+    pub fn set_camera(&self, camera: camera::Camera) {
+        self.camera_controller.borrow_mut().look_from(&camera);
+        self.write_camera(camera);
+    }
+
+    /// Look through `camera` of a camera node or a camera track, every frame.
+    ///
+    /// The input given to the controller meanwhile is kept, once it moves the camera
+    /// the scene stops placing it until the next [`State::load_scene`].
+    pub(crate) fn follow_camera(&self, camera: camera::Camera) {
+        if !self.camera_follows_scene.get() {
+            return;
+        }
+        self.camera_controller.borrow_mut().follow(&camera);
+        self.write_camera(camera);
+    }
+
+    /// Bounds in world space of the models under `nodes` and their descendants,
+    /// `None` if they hold no model.
+    pub fn world_bounds(&self, nodes: &[NodeId]) -> Option<(Aabb, BoundingSphere)> {
+        let scene = self.scene.borrow();
+        let world = scene.world_matrices();
+
+        let mut bounds = None;
+        let mut stack = nodes.to_vec();
+        while let Some(id) = stack.pop() {
+            let node = match scene.node(id) {
+                Some(node) => node,
+                None => continue,
+            };
+            stack.extend_from_slice(node.children());
+
+            let model = match &node.content {
+                Some(NodeContent::Model(key)) => self.obj_models.get(key),
+                _ => None,
+            };
+            let (model, sphere) = match model.and_then(|m| Some((m, m.bounding_sphere?))) {
+                Some(model) => model,
+                None => continue,
+            };
+            let aabb = model.aabb.transformed(&world[id.index()]);
+            let sphere = sphere.transformed(&world[id.index()]);
+            bounds = Some(match bounds {
+                Some((a, s)) => (Aabb::union(a, aabb), BoundingSphere::union(s, sphere)),
+                None => (aabb, sphere),
+            });
+        }
+
+        bounds
+    }
+
+    /// Frame every model of the scene, returns false if there is none.
+    pub fn frame_all(&self) -> bool {
+        let roots = self
+            .scene
+            .borrow()
+            .iter()
+            .filter(|(_, n)| n.parent().is_none())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        self.frame_nodes(&roots)
+    }
+
+    /// Frame the models under `nodes` and their descendants, returns false if they hold none.
+    pub fn frame_nodes(&self, nodes: &[NodeId]) -> bool {
+        match self.world_bounds(nodes) {
+            Some((_, sphere)) => {
+                self.frame(sphere);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Look at all of `sphere` from the direction the camera looks in now,
+    /// the near and far planes and the dolly limits of the controller follow its size.
+    pub fn frame(&self, sphere: BoundingSphere) {
+        let camera = {
+            let settings = &mut self.camera_controller.borrow_mut().settings;
+            self.camera.get().frame(sphere, settings)
         };
+        self.set_camera(camera);
+    }
 
+    fn write_camera(&self, camera: camera::Camera) {
         self.camera.set(camera);
 
         // effect camera change
-        {
-            let mut camera_uniform = self.camera_uniform.get();
-            camera_uniform.update_view_proj(&camera);
-            self.camera_uniform.set(camera_uniform);
-
-            self.queue.write_buffer(
-                &self.camera_buffer,
-                0,
-                bytemuck::cast_slice(&[self.camera_uniform.get()]),
-            );
-        }
+        let mut camera_uniform = self.camera_uniform.get();
+        camera_uniform.update_view_proj(&camera);
+        self.camera_uniform.set(camera_uniform);
 
-        self.render()
-    }
-
-    /// # TODO
-    ///  - add time difference as attribute for f
-    ///  - fix preemption problem if possible
-    pub fn animation_insert(&self, lable: String, f: Box<dyn for<'r> Fn(&'r State)>) {
-        if let Some(state) = unsafe { STATE.get() } {
-            if let Some((anima_loop, _)) = state.animation.get() {
-                anima_loop.borrow_mut().insert(lable, f);
-            } else {
-                state.animation.get_or_init(|| {
-                    (
-                        {
-                            let mut hashmap = HashMap::new();
-                            hashmap.insert(lable, f);
-                            RefCell::new(hashmap)
-                        },
-                        gloo::timers::callback::Interval::new(17, || {
-                            if let Some((animation_loop, _)) = state.animation.get() {
-                                animation_loop.borrow().iter().for_each(|(_, f)| f(state));
-                            };
-
-                            state.render().unwrap();
-                        }),
-                    )
-                });
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[self.camera_uniform.get()]),
+        );
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<instance::InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Place the lights, the camera and the model instances where the scene nodes are,
+    /// returns the instances of every model and the world matrix of every instance.
+    fn update_scene(&self) -> (InstanceRanges, Vec<cgmath::Matrix4<f32>>) {
+        let scene = self.scene.borrow();
+        let world = scene.world_matrices();
+
+        let mut instances = HashMap::<&str, Vec<cgmath::Matrix4<f32>>>::new();
+        let mut light_placements = Vec::new();
+        for (content, matrix) in scene.placements(&world) {
+            match content {
+                NodeContent::Model(key) => instances.entry(key).or_default().push(matrix),
+                NodeContent::Light(index) => {
+                    light_placements.push((*index, scene::eye_and_target(matrix)));
+                }
+                NodeContent::Camera => {
+                    let (eye, target) = scene::eye_and_target(matrix);
+                    self.follow_camera(camera::Camera {
+                        eye,
+                        target,
+                        up: matrix.y.truncate(),
+                        ..self.camera.get()
+                    });
+                }
             }
         }
-    }
 
-    pub fn animation_clear(&self) {
-        if let Some(state) = unsafe { STATE.get() } {
-            if let Some((animation_loop, _)) = state.animation.get() {
-                animation_loop.borrow_mut().clear();
+        // The light nodes upload the lights once, if they moved any
+        let mut moved = false;
+        for (index, (position, target)) in light_placements {
+            if let Some(light) = self.lights.borrow_mut().get_mut(index) {
+                let direction = target - position;
+                moved |= light.position != position || light.direction != direction;
+                light.position = position;
+                light.direction = direction;
             }
         }
+        if moved {
+            self.write_lights();
+        }
+
+        let mut matrices = Vec::new();
+        let mut ranges = Vec::new();
+        for (key, model_matrices) in instances {
+            let start = matrices.len() as u32;
+            matrices.extend(model_matrices);
+            ranges.push((key.to_owned(), start..matrices.len() as u32));
+        }
+
+        if matrices.is_empty() {
+            return (ranges, matrices);
+        }
+
+        let instance_data = matrices
+            .iter()
+            .map(|&matrix| instance::InstanceRaw::from_matrix(matrix))
+            .collect::<Vec<_>>();
+
+        let size = std::mem::size_of_val(instance_data.as_slice()) as wgpu::BufferAddress;
+        if self.instance_buffer.borrow().size() < size {
+            *self.instance_buffer.borrow_mut() =
+                Self::create_instance_buffer(&self.device, instance_data.len().next_power_of_two());
+        }
+        self.queue.write_buffer(
+            &self.instance_buffer.borrow(),
+            0,
+            bytemuck::cast_slice(&instance_data),
+        );
+
+        (ranges, matrices)
     }
 
     pub fn render(&self) -> PlayerErrorResult<()> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (instance_ranges, instance_matrices) = self.update_scene();
+
+        if self
+            .shadow_maps
+            .borrow_mut()
+            .fit(&self.device, &self.lights.borrow())
+        {
+            // Lights cover less of the larger layers
+            self.rebuild_light_bind_group();
+        }
+
+        let frame = self.target.borrow().current_frame()?;
+        let depth_texture = self.depth_texture.borrow();
+        let instance_buffer = self.instance_buffer.borrow();
+        let light_bind_group = self.light_bind_group.borrow();
+        let background = self.background.borrow();
+        background.update(&self.queue, &self.camera.get());
+        let post = self.post.borrow();
+        let pipelines = self.pipelines.borrow();
+        let (frame_view, resolve_target) = post.frame_attachment();
 
         let mut encoder = self
             .device
@@ -469,24 +920,31 @@ impl State {
                 label: Some("Render Encoder"),
             });
 
+        let models = instance_ranges
+            .iter()
+            .filter_map(|(key, instances)| Some((self.obj_models.get(key)?, instances.clone())))
+            .collect::<Vec<_>>();
+        self.shadow_maps.borrow().render(
+            &mut encoder,
+            &self.queue,
+            &self.lights.borrow(),
+            &models,
+            &instance_buffer,
+        );
+
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
+                    view: frame_view,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.0,
-                            g: 0.0,
-                            b: 0.0,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(background.clear_color()),
                         store: true,
                     },
                 })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
+                    view: &depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(1.0),
                         store: true,
@@ -497,32 +955,98 @@ impl State {
 
             // render()
 
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            background.draw(&mut render_pass);
+
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
 
             use light::DrawLight;
-            render_pass.set_pipeline(&self.light_render_pipeline);
-            render_pass.draw_light_model(
-                &self.obj_models.get("cube").unwrap(),
+            render_pass.set_pipeline(&pipelines.light);
+            let light_count = self.lights.borrow().len().min(light::MAX_LIGHTS) as u32;
+            render_pass.draw_light_model_instanced(
+                &self.light_model,
+                0..light_count,
                 &self.camera_bind_group,
-                &self.light_bind_group,
+                &light_bind_group,
             );
 
-            render_pass.set_pipeline(&self.yueqin_render_pipeline);
-            model::draw_trait::DrawModel::draw_model_instanced(
-                &mut render_pass,
-                &self.obj_models.get("yueqin").unwrap(),
-                0..self.instances.len() as u32,
-                &self.camera_bind_group,
-                &self.light_bind_group,
-            );
+            for kind in [model::MaterialKind::Phong, model::MaterialKind::Pbr] {
+                render_pass.set_pipeline(pipelines.model(kind, false));
+                for (obj_model, instances) in &models {
+                    model::draw_trait::DrawModel::draw_model_instanced_of_kind(
+                        &mut render_pass,
+                        obj_model,
+                        kind,
+                        instances.clone(),
+                        &self.camera_bind_group,
+                        &light_bind_group,
+                    );
+                }
+            }
+
+            // Transparent meshes one instance at a time, the farthest first
+            let eye = self.camera.get().eye;
+            let mut transparent = Vec::new();
+            for (obj_model, instances) in &models {
+                for mesh in &obj_model.meshes {
+                    if !obj_model.materials[mesh.material].blend {
+                        continue;
+                    }
+                    for instance in instances.clone() {
+                        let center = instance_matrices[instance as usize]
+                            .transform_point(mesh.aabb.center());
+                        transparent.push((center.distance2(eye), *obj_model, mesh, instance));
+                    }
+                }
+            }
+            transparent.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+            let mut bound_kind = None;
+            for (_, obj_model, mesh, instance) in transparent {
+                let material = &obj_model.materials[mesh.material];
+                if bound_kind != Some(material.kind) {
+                    render_pass.set_pipeline(pipelines.model(material.kind, true));
+                    bound_kind = Some(material.kind);
+                }
+                model::draw_trait::DrawModel::draw_mesh_instanced(
+                    &mut render_pass,
+                    mesh,
+                    material,
+                    obj_model.skin_of(mesh),
+                    instance..instance + 1,
+                    &self.camera_bind_group,
+                    &light_bind_group,
+                );
+            }
         }
 
+        // Multisampled depth can't be sampled on WebGL
+        let depth = (post.sample_count() == 1).then_some(&depth_texture.view);
+        post.run(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            depth,
+            camera::CameraUniform::projection(&self.camera.get()),
+            &frame.view,
+        );
+
         // submit will accept anything that implements IntoIter
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        frame.present();
 
         Ok(())
     }
+
+    /// Pixels of the last rendered frame, for states built by [`State::new_headless`].
+    pub async fn read_pixels(&self) -> PlayerErrorResult<RgbaImage> {
+        let readback = self.target.borrow().start_readback(
+            &self.device,
+            &self.queue,
+            &self.config.borrow(),
+        )?;
+
+        readback.into_image(&self.device).await
+    }
 }
 
 pub(crate) trait Vertex {
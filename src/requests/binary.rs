@@ -1,12 +1,18 @@
-use gloo::net::http::Request;
-
 use super::error::RequestResult;
 
+#[cfg(target_arch = "wasm32")]
 pub async fn request_binary(path: &str) -> RequestResult<Vec<u8>> {
+    use gloo::net::http::Request;
+
     Ok(Request::get(path)
         .header("responseType", "blob")
         .send()
         .await?
         .binary()
         .await?)
-}
\ No newline at end of file
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn request_binary(path: &str) -> RequestResult<Vec<u8>> {
+    Ok(std::fs::read(super::local::local_path(path))?)
+}
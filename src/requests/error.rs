@@ -6,4 +6,8 @@ pub type RequestResult<T> = Result<T, RequestError>;
 pub enum RequestError {
     #[error("Fail to have a net request.")]
     NetRequestError(#[from] gloo::net::Error),
+    #[error("Fail to read a local file.")]
+    IoError(#[from] std::io::Error),
+    #[error("Fail to decode an image.")]
+    ImageError(#[from] image::ImageError),
 }
@@ -11,21 +11,27 @@ pub struct Image {
 
 impl Image {
     pub async fn from_name(name: &str) -> RequestResult<Self> {
-        Ok(Self::from_bytes(
-            &request_binary(&format!("/static/image/{}", name)).await?,
-        ))
+        Self::from_bytes(&request_binary(&format!("/static/image/{}", name)).await?)
     }
 
-    pub fn from_bytes(diffuse_bytes: &[u8]) -> Self {
-        let diffuse_image = image::load_from_memory(diffuse_bytes).unwrap();
+    pub fn from_bytes(diffuse_bytes: &[u8]) -> RequestResult<Self> {
+        let diffuse_image = image::load_from_memory(diffuse_bytes)?;
         let diffuse_rgba = diffuse_image.to_rgba8();
 
         use image::GenericImageView;
         let dimensions = diffuse_image.dimensions();
 
-        Self {
+        Ok(Self {
             diffuse_rgba,
             dimensions,
+        })
+    }
+
+    /// A single pixel image, used where a model provides no texture.
+    pub fn from_color(rgba: [u8; 4]) -> Self {
+        Self {
+            diffuse_rgba: RgbaImage::from_pixel(1, 1, image::Rgba(rgba)),
+            dimensions: (1, 1),
         }
     }
 
@@ -36,4 +42,48 @@ impl Image {
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
+
+    /// No pixel lets anything behind it through.
+    pub fn is_opaque(&self) -> bool {
+        self.diffuse_rgba.pixels().all(|p| p[3] == u8::MAX)
+    }
+}
+
+/// High dynamic range image in linear rgb, like an `.hdr` environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrImage {
+    /// Rows from the top, alpha is always 1
+    pixels: Vec<[f32; 4]>,
+    /// (width, height) of image texture
+    dimensions: (u32, u32),
+}
+
+impl HdrImage {
+    /// Radiance `.hdr` file inside `static/image`.
+    pub async fn from_name(name: &str) -> RequestResult<Self> {
+        Self::from_bytes(&request_binary(&format!("/static/image/{}", name)).await?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> RequestResult<Self> {
+        let decoder = image::codecs::hdr::HdrDecoder::new(bytes)?;
+        let metadata = decoder.metadata();
+        let pixels = decoder
+            .read_image_hdr()?
+            .into_iter()
+            .map(|image::Rgb([r, g, b])| [r, g, b, 1.0])
+            .collect();
+
+        Ok(Self {
+            pixels,
+            dimensions: (metadata.width, metadata.height),
+        })
+    }
+
+    pub fn pixels(&self) -> &[[f32; 4]] {
+        &self.pixels
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
 }
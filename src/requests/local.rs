@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use once_cell::sync::OnceCell;
+
+static ASSET_ROOT: OnceCell<PathBuf> = OnceCell::new();
+
+/// Set the directory holding `static/` when running outside a browser.
+///
+/// Defaults to the working directory, can only be set once.
+pub fn set_asset_root(root: impl Into<PathBuf>) -> bool {
+    ASSET_ROOT.set(root.into()).is_ok()
+}
+
+/// Map a site path such as `/static/obj/cube.obj` onto the local file system.
+pub(super) fn local_path(path: &str) -> PathBuf {
+    let relative = path.trim_start_matches('/');
+
+    match ASSET_ROOT.get() {
+        Some(root) => root.join(relative),
+        None => PathBuf::from(relative),
+    }
+}
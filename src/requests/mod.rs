@@ -4,7 +4,13 @@ pub use self::text::*;
 pub mod image;
 pub use self::image::*;
 
-mod binary;
+pub mod binary;
+pub use self::binary::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod local;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::local::set_asset_root;
 
 pub mod error;
-pub use self::error::*;
\ No newline at end of file
+pub use self::error::*;
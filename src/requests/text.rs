@@ -1,12 +1,18 @@
-use gloo::net::http::Request;
-
 use super::error::RequestResult;
 
+#[cfg(target_arch = "wasm32")]
 pub async fn request_string(path: &str) -> RequestResult<String> {
+    use gloo::net::http::Request;
+
     Ok(Request::get(path)
         .header("responseType", "blob")
         .send()
         .await?
         .text()
         .await?)
-}
\ No newline at end of file
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn request_string(path: &str) -> RequestResult<String> {
+    Ok(std::fs::read_to_string(super::local::local_path(path))?)
+}
@@ -0,0 +1,55 @@
+// Draws the background behind the models, one triangle covering the view
+
+struct Background {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+    // Camera axes in world space, scaled to the edges of the view
+    forward: vec4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> background: Background;
+@group(0) @binding(1)
+var t_image: texture_2d<f32>;
+@group(0) @binding(2)
+var t_cube: texture_cube<f32>;
+@group(0) @binding(3)
+var s_background: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // -1, -1 at the bottom left of the view
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 1.0, 1.0);
+    out.ndc = vec2<f32>(x, y);
+    return out;
+}
+
+@fragment
+fn fs_gradient(in: VertexOutput) -> @location(0) vec4<f32> {
+    return mix(background.bottom, background.top, in.ndc.y * 0.5 + 0.5);
+}
+
+// Stretched over the view
+@fragment
+fn fs_image(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.ndc * vec2<f32>(0.5, -0.5) + 0.5;
+    return textureSampleLevel(t_image, s_background, uv, 0.0);
+}
+
+// Seen from the center, wherever the camera is
+@fragment
+fn fs_cube(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = background.forward.xyz + in.ndc.x * background.right.xyz + in.ndc.y * background.up.xyz;
+    return vec4<f32>(textureSampleLevel(t_cube, s_background, dir, 0.0).rgb, 1.0);
+}
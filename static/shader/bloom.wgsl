@@ -0,0 +1,95 @@
+// Glow around the bright parts of the frame, blurred over a chain of halved textures
+
+struct Bloom {
+    threshold: f32,
+    intensity: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> bloom: Bloom;
+// Level sampled by the pass
+@group(0) @binding(1)
+var t_source: texture_2d<f32>;
+// Frame the glow is added to
+@group(0) @binding(2)
+var t_scene: texture_2d<f32>;
+@group(0) @binding(3)
+var s_bloom: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0, 0 at the top left of the target
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+fn tap(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSampleLevel(t_source, s_bloom, uv + texel * vec2<f32>(x, y), 0.0).rgb;
+}
+
+// 13 taps weighted like in Call of Duty: Advanced Warfare, small bright spots don't flicker
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let corners = tap(uv, texel, -2.0, -2.0) + tap(uv, texel, 2.0, -2.0)
+        + tap(uv, texel, -2.0, 2.0) + tap(uv, texel, 2.0, 2.0);
+    let edges = tap(uv, texel, 0.0, -2.0) + tap(uv, texel, -2.0, 0.0)
+        + tap(uv, texel, 2.0, 0.0) + tap(uv, texel, 0.0, 2.0);
+    let inner = tap(uv, texel, -1.0, -1.0) + tap(uv, texel, 1.0, -1.0)
+        + tap(uv, texel, -1.0, 1.0) + tap(uv, texel, 1.0, 1.0);
+    let center = tap(uv, texel, 0.0, 0.0);
+
+    return center * 0.125 + inner * 0.125 + edges * 0.0625 + corners * 0.03125;
+}
+
+// Part of the color above the threshold, with a soft knee from half of it
+fn bright(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = bloom.threshold * 0.5;
+    var soft = clamp(brightness - bloom.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 0.0001);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 0.0001);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(bright(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, added onto the larger level
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_source));
+
+    let corners = tap(in.uv, texel, -1.0, -1.0) + tap(in.uv, texel, 1.0, -1.0)
+        + tap(in.uv, texel, -1.0, 1.0) + tap(in.uv, texel, 1.0, 1.0);
+    let edges = tap(in.uv, texel, 0.0, -1.0) + tap(in.uv, texel, -1.0, 0.0)
+        + tap(in.uv, texel, 1.0, 0.0) + tap(in.uv, texel, 0.0, 1.0);
+    let center = tap(in.uv, texel, 0.0, 0.0);
+
+    return vec4<f32>((center * 4.0 + edges * 2.0 + corners) / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene = textureSampleLevel(t_scene, s_bloom, in.uv, 0.0);
+    let glow = textureSampleLevel(t_source, s_bloom, in.uv, 0.0).rgb;
+    return vec4<f32>(scene.rgb + glow * bloom.intensity, scene.a);
+}
@@ -10,11 +10,56 @@ var<uniform> camera: Camera;
 
 struct Light {
     position: vec3<f32>,
+    // 0 point, 1 directional, 2 spot
+    kind: u32,
+    direction: vec3<f32>,
+    // 0 for lights without range
+    range: f32,
     color: vec3<f32>,
+    intensity: f32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    shadow_matrix: mat4x4<f32>,
+    // -1 for lights without shadow
+    shadow_layer: i32,
+    shadow_bias: f32,
+    // Part of the layer the light renders into
+    shadow_scale: f32,
+    shadow_pcf_radius: i32,
+}
+
+// Same as MAX_LIGHTS of light.rs
+struct Lights {
+    lights: array<Light, 16>,
+    count: u32,
+    // 0 without an environment
+    environment_intensity: f32,
+    environment_mip_count: f32,
 }
 
 @group(2) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+@group(2) @binding(3)
+var t_irradiance: texture_cube<f32>;
+@group(2) @binding(4)
+var t_prefiltered: texture_cube<f32>;
+// Scale in r and bias in g of f0, by n·v and roughness
+@group(2) @binding(5)
+var t_brdf_lut: texture_2d<f32>;
+@group(2) @binding(6)
+var s_environment: sampler;
+
+// Same as MAX_JOINTS of skeleton.rs
+struct Skin {
+    joint_matrices: array<mat4x4<f32>, 128>,
+}
+
+@group(3) @binding(0)
+var<uniform> skin: Skin;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -22,6 +67,8 @@ struct VertexInput {
     @location(2) normal: vec3<f32>,
     @location(3) tangent: vec3<f32>,
     @location(4) bitangent: vec3<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
 };
 
 struct InstanceInput {
@@ -37,9 +84,10 @@ struct InstanceInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) tangent_position: vec3<f32>,
-    @location(2) tangent_light_position: vec3<f32>,
-    @location(3) tangent_view_position: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
 };
 
 @vertex
@@ -60,24 +108,102 @@ fn vs_main(
         instance.normal_matrix_2,
     );
 
-    // Construct the tangent matrix
-    let world_normal = normalize(normal_matrix * model.normal);
-    let world_tangent = normalize(normal_matrix * model.tangent);
-    let world_bitangent = normalize(normal_matrix * model.bitangent);
-    let tangent_matrix = transpose(mat3x3<f32>(
-        world_tangent,
-        world_bitangent,
-        world_normal,
-    ));
+    // Blend the joint matrices, vertices without weights are not skinned
+    var skin_matrix = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    if (dot(model.weights, vec4<f32>(1.0)) > 0.0) {
+        skin_matrix = skin.joint_matrices[min(model.joints.x, 127u)] * model.weights.x
+            + skin.joint_matrices[min(model.joints.y, 127u)] * model.weights.y
+            + skin.joint_matrices[min(model.joints.z, 127u)] * model.weights.z
+            + skin.joint_matrices[min(model.joints.w, 127u)] * model.weights.w;
+    }
+    // Good enough for joints without non uniform scale
+    let skin_normal_matrix = mat3x3<f32>(
+        skin_matrix[0].xyz,
+        skin_matrix[1].xyz,
+        skin_matrix[2].xyz,
+    );
 
-    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    let world_position = model_matrix * skin_matrix * vec4<f32>(model.position, 1.0);
 
+    // Lights are summed up in world space, the fragment shader builds the tangent matrix
     var out: VertexOutput;
     out.clip_position = camera.view_proj * world_position;
     out.tex_coords = model.tex_coords;
-    out.tangent_position = tangent_matrix * world_position.xyz;
-    out.tangent_view_position = tangent_matrix * camera.view_pos.xyz;
-    out.tangent_light_position = tangent_matrix * light.position;
+    out.world_position = world_position.xyz;
+    out.world_normal = normalize(normal_matrix * skin_normal_matrix * model.normal);
+    out.world_tangent = normalize(normal_matrix * skin_normal_matrix * model.tangent);
+    out.world_bitangent = normalize(normal_matrix * skin_normal_matrix * model.bitangent);
+    return out;
+}
+
+// Direction towards a light and the light arriving from it
+struct Incoming {
+    direction: vec3<f32>,
+    radiance: vec3<f32>,
+    // 0 in the shadow, 1 in the light
+    shadow: f32,
+}
+
+// Percentage closer filtered lookup of the shadow map
+fn shadow(light: Light, world_position: vec3<f32>) -> f32 {
+    if (light.shadow_layer < 0) {
+        return 1.0;
+    }
+
+    let clip = light.shadow_matrix * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    // Outside of what the light sees
+    if (clip.w <= 0.0 || ndc.z > 1.0 || abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0) {
+        return 1.0;
+    }
+
+    let uv = (ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * light.shadow_scale;
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    let depth = ndc.z - light.shadow_bias;
+
+    var lit = 0.0;
+    var samples = 0.0;
+    for (var y = -light.shadow_pcf_radius; y <= light.shadow_pcf_radius; y = y + 1) {
+        for (var x = -light.shadow_pcf_radius; x <= light.shadow_pcf_radius; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, light.shadow_layer, depth);
+            samples = samples + 1.0;
+        }
+    }
+
+    return lit / samples;
+}
+
+fn incoming(light: Light, world_position: vec3<f32>) -> Incoming {
+    var out: Incoming;
+    out.radiance = light.color * light.intensity;
+    out.shadow = shadow(light, world_position);
+
+    if (light.kind == 1u) {
+        out.direction = -light.direction;
+        return out;
+    }
+
+    let to_light = light.position - world_position;
+    let distance = length(to_light);
+    out.direction = to_light / distance;
+
+    // Smooth window of KHR_lights_punctual, reaching 0 at the range
+    if (light.range > 0.0) {
+        let falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+        out.radiance = out.radiance * falloff * falloff;
+    }
+    if (light.kind == 2u) {
+        let cos_angle = dot(light.direction, -out.direction);
+        out.radiance = out.radiance
+            * smoothstep(light.outer_cone_cos, light.inner_cone_cos, cos_angle);
+    }
+
     return out;
 }
 
@@ -92,28 +218,67 @@ var t_normal: texture_2d<f32>;
 @group(0) @binding(3)
 var s_normal: sampler;
 
+// Parameters of the MTL file
+struct Material {
+    ambient: vec3<f32>,
+    shininess: f32,
+    diffuse: vec3<f32>,
+    dissolve: f32,
+    specular: vec3<f32>,
+    optical_density: f32,
+    emissive: vec3<f32>,
+}
+
+@group(0) @binding(4)
+var<uniform> material: Material;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let object_color: vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords)
+        * vec4<f32>(material.diffuse, material.dissolve);
     let object_normal: vec4<f32> = textureSample(t_normal, s_normal, in.tex_coords);
-    
+
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    let normal = normalize(tangent_matrix * (object_normal.xyz * 2.0 - 1.0));
+    let view_dir = normalize(camera.view_pos.xyz - in.world_position);
+    let ambient_color = material.ambient * object_color.rgb;
+
     // We don't need (or want) much ambient light, so 0.1 is fine
-    let ambient_strength = 0.1;
-    let ambient_color = light.color * ambient_strength;
+    var ambient_strength = 0.1;
+    var result = material.emissive;
+    // The environment replaces the ambient light of every light
+    if (lights.environment_intensity > 0.0) {
+        ambient_strength = 0.0;
 
-    // Create the lighting vectors
-    let tangent_normal = object_normal.xyz * 2.0 - 1.0;
-    let light_dir = normalize(in.tangent_light_position - in.tangent_position);
-    let view_dir = normalize(in.tangent_view_position - in.tangent_position);
-    let half_dir = normalize(view_dir + light_dir);
+        // Schlick's approximation with the reflectance of the index of refraction
+        let f0 = pow((material.optical_density - 1.0) / (material.optical_density + 1.0), 2.0);
+        let fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(normal, view_dir), 0.0), 5.0);
+        // Roughness of the Beckmann lobe matching the highlight
+        let roughness = sqrt(2.0 / (material.shininess + 2.0));
+        let lod = roughness * (lights.environment_mip_count - 1.0);
+        let reflection = textureSampleLevel(t_prefiltered, s_environment, reflect(-view_dir, normal), lod).rgb;
 
-    let diffuse_strength = max(dot(tangent_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+        result = result + (
+            textureSampleLevel(t_irradiance, s_environment, normal, 0.0).rgb * ambient_color
+                + reflection * material.specular * fresnel
+        ) * lights.environment_intensity;
+    }
+    for (var i = 0u; i < min(lights.count, 16u); i = i + 1u) {
+        let light = incoming(lights.lights[i], in.world_position);
+        let half_dir = normalize(view_dir + light.direction);
 
-    let specular_strength = pow(max(dot(tangent_normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+        let diffuse_strength = max(dot(normal, light.direction), 0.0);
+        let specular_strength = pow(max(dot(normal, half_dir), 0.0), material.shininess);
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+        result = result + (
+            ambient_strength * ambient_color
+                + (diffuse_strength * object_color.rgb + specular_strength * material.specular) * light.shadow
+        ) * light.radiance;
+    }
 
     return vec4<f32>(result, object_color.a);
 }
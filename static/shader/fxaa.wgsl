@@ -0,0 +1,181 @@
+// FXAA 3.11 of Timothy Lottes on the tonemapped frame, after the quality preset:
+// edges are found from the contrast of the luma and blended along their direction
+
+@group(0) @binding(0)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(1)
+var s_frame: sampler;
+
+let EDGE_THRESHOLD_MIN: f32 = 0.0312;
+let EDGE_THRESHOLD_MAX: f32 = 0.125;
+let SUBPIXEL_QUALITY: f32 = 0.75;
+let ITERATIONS: i32 = 12;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0, 0 at the top left of the target
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+// Farther steps of the search along the edge
+fn quality(i: i32) -> f32 {
+    if (i < 5) {
+        return 1.0;
+    } else if (i == 5) {
+        return 1.5;
+    } else if (i < 10) {
+        return 2.0;
+    } else if (i == 10) {
+        return 4.0;
+    }
+    return 8.0;
+}
+
+// Perceived brightness, the frame is sampled in linear space
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn luma_at(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(t_frame, s_frame, uv, 0.0).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_frame));
+    let center = textureSampleLevel(t_frame, s_frame, in.uv, 0.0);
+
+    let luma_center = luma(center.rgb);
+    let luma_down = luma_at(in.uv + vec2<f32>(0.0, 1.0) * texel);
+    let luma_up = luma_at(in.uv + vec2<f32>(0.0, -1.0) * texel);
+    let luma_left = luma_at(in.uv + vec2<f32>(-1.0, 0.0) * texel);
+    let luma_right = luma_at(in.uv + vec2<f32>(1.0, 0.0) * texel);
+
+    let luma_min = min(luma_center, min(min(luma_down, luma_up), min(luma_left, luma_right)));
+    let luma_max = max(luma_center, max(max(luma_down, luma_up), max(luma_left, luma_right)));
+    let luma_range = luma_max - luma_min;
+
+    // Not on an edge, or too dark to see it
+    if (luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX)) {
+        return center;
+    }
+
+    let luma_down_left = luma_at(in.uv + vec2<f32>(-1.0, 1.0) * texel);
+    let luma_up_right = luma_at(in.uv + vec2<f32>(1.0, -1.0) * texel);
+    let luma_up_left = luma_at(in.uv + vec2<f32>(-1.0, -1.0) * texel);
+    let luma_down_right = luma_at(in.uv + vec2<f32>(1.0, 1.0) * texel);
+
+    let luma_down_up = luma_down + luma_up;
+    let luma_left_right = luma_left + luma_right;
+    let luma_left_corners = luma_down_left + luma_up_left;
+    let luma_down_corners = luma_down_left + luma_down_right;
+    let luma_right_corners = luma_down_right + luma_up_right;
+    let luma_up_corners = luma_up_right + luma_up_left;
+
+    let edge_horizontal = abs(-2.0 * luma_left + luma_left_corners)
+        + abs(-2.0 * luma_center + luma_down_up) * 2.0
+        + abs(-2.0 * luma_right + luma_right_corners);
+    let edge_vertical = abs(-2.0 * luma_up + luma_up_corners)
+        + abs(-2.0 * luma_center + luma_left_right) * 2.0
+        + abs(-2.0 * luma_down + luma_down_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // Which side of the pixel the edge is on
+    var luma_1 = luma_left;
+    var luma_2 = luma_right;
+    var step_length = texel.x;
+    if (is_horizontal) {
+        luma_1 = luma_up;
+        luma_2 = luma_down;
+        step_length = texel.y;
+    }
+    let gradient_1 = luma_1 - luma_center;
+    let gradient_2 = luma_2 - luma_center;
+    let is_1_steepest = abs(gradient_1) >= abs(gradient_2);
+    let gradient_scaled = 0.25 * max(abs(gradient_1), abs(gradient_2));
+
+    var luma_local_average = 0.5 * (luma_2 + luma_center);
+    if (is_1_steepest) {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_1 + luma_center);
+    }
+
+    // Half a texel onto the edge, then along it both ways
+    var current_uv = in.uv;
+    var offset = vec2<f32>(texel.x, 0.0);
+    if (is_horizontal) {
+        current_uv.y = current_uv.y + step_length * 0.5;
+    } else {
+        current_uv.x = current_uv.x + step_length * 0.5;
+        offset = vec2<f32>(0.0, texel.y);
+    }
+
+    var uv_1 = current_uv - offset;
+    var uv_2 = current_uv + offset;
+    var luma_end_1 = luma_at(uv_1) - luma_local_average;
+    var luma_end_2 = luma_at(uv_2) - luma_local_average;
+    var reached_1 = abs(luma_end_1) >= gradient_scaled;
+    var reached_2 = abs(luma_end_2) >= gradient_scaled;
+
+    for (var i = 1; i < ITERATIONS && !(reached_1 && reached_2); i = i + 1) {
+        if (!reached_1) {
+            uv_1 = uv_1 - offset * quality(i);
+            luma_end_1 = luma_at(uv_1) - luma_local_average;
+            reached_1 = abs(luma_end_1) >= gradient_scaled;
+        }
+        if (!reached_2) {
+            uv_2 = uv_2 + offset * quality(i);
+            luma_end_2 = luma_at(uv_2) - luma_local_average;
+            reached_2 = abs(luma_end_2) >= gradient_scaled;
+        }
+    }
+
+    var distance_1 = in.uv.x - uv_1.x;
+    var distance_2 = uv_2.x - in.uv.x;
+    if (!is_horizontal) {
+        distance_1 = in.uv.y - uv_1.y;
+        distance_2 = uv_2.y - in.uv.y;
+    }
+    let is_direction_1 = distance_1 < distance_2;
+    let distance_final = min(distance_1, distance_2);
+    let edge_thickness = distance_1 + distance_2;
+
+    // Only blend when the nearer end of the edge varies the other way than the center
+    let is_luma_center_smaller = luma_center < luma_local_average;
+    var luma_end = luma_end_2;
+    if (is_direction_1) {
+        luma_end = luma_end_1;
+    }
+    var pixel_offset = 0.0;
+    if ((luma_end < 0.0) != is_luma_center_smaller) {
+        pixel_offset = -distance_final / edge_thickness + 0.5;
+    }
+
+    // Aliasing finer than a pixel, from the average of the neighbours
+    let luma_average = (1.0 / 12.0) * (2.0 * (luma_down_up + luma_left_right)
+        + luma_left_corners + luma_right_corners);
+    let subpixel_offset = clamp(abs(luma_average - luma_center) / luma_range, 0.0, 1.0);
+    let subpixel = (-2.0 * subpixel_offset + 3.0) * subpixel_offset * subpixel_offset;
+    pixel_offset = max(pixel_offset, subpixel * subpixel * SUBPIXEL_QUALITY);
+
+    var final_uv = in.uv;
+    if (is_horizontal) {
+        final_uv.y = final_uv.y + pixel_offset * step_length;
+    } else {
+        final_uv.x = final_uv.x + pixel_offset * step_length;
+    }
+
+    return vec4<f32>(textureSampleLevel(t_frame, s_frame, final_uv, 0.0).rgb, center.a);
+}
@@ -0,0 +1,184 @@
+// Bakes the maps of image based lighting, one face of a cube per pass
+
+struct Params {
+    // +x, -x, +y, -y, +z, -z
+    face: u32,
+    roughness: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+// Source of fs_equirect
+@group(0) @binding(1)
+var t_equirect: texture_2d<f32>;
+@group(0) @binding(2)
+var s_equirect: sampler;
+// Source of the other passes
+@group(0) @binding(3)
+var t_cube: texture_cube<f32>;
+@group(0) @binding(4)
+var s_cube: sampler;
+
+let PI: f32 = 3.14159265359;
+let SAMPLE_COUNT: u32 = 256u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0, 0 at the top left of the target
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+// Direction through a point of a cube face
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+
+    var dir = vec3<f32>(-s, -t, -1.0);
+    if (face == 0u) {
+        dir = vec3<f32>(1.0, -t, -s);
+    } else if (face == 1u) {
+        dir = vec3<f32>(-1.0, -t, s);
+    } else if (face == 2u) {
+        dir = vec3<f32>(s, 1.0, t);
+    } else if (face == 3u) {
+        dir = vec3<f32>(s, -1.0, -t);
+    } else if (face == 4u) {
+        dir = vec3<f32>(s, -t, 1.0);
+    }
+
+    return normalize(dir);
+}
+
+@fragment
+fn fs_equirect(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = face_direction(params.face, in.uv);
+    let uv = vec2<f32>(
+        atan2(dir.z, dir.x) / (2.0 * PI) + 0.5,
+        acos(clamp(dir.y, -1.0, 1.0)) / PI,
+    );
+
+    return vec4<f32>(textureSampleLevel(t_equirect, s_equirect, uv, 0.0).rgb, 1.0);
+}
+
+// Cosine weighted sum over the hemisphere around the normal
+@fragment
+fn fs_irradiance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = face_direction(params.face, in.uv);
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(n.y) > 0.99) {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    let delta = 0.05;
+    var sum = vec3<f32>(0.0);
+    var samples = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi = phi + delta) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta = theta + delta) {
+            let tangent = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let dir = tangent.x * right + tangent.y * up + tangent.z * n;
+            sum = sum + textureSampleLevel(t_cube, s_cube, dir, 0.0).rgb * cos(theta) * sin(theta);
+            samples = samples + 1.0;
+        }
+    }
+
+    return vec4<f32>(PI * sum / samples, 1.0);
+}
+
+// Van der Corput sequence, reverseBits is missing on WebGL
+fn radical_inverse(bits_in: u32) -> f32 {
+    var bits = bits_in;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(i: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(SAMPLE_COUNT), radical_inverse(i));
+}
+
+// Half vector around n, distributed like the GGX lobe of roughness
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let h = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(n.z) < 0.999);
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+
+// Radiance reflected by a GGX lobe, looking along the normal
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n = face_direction(params.face, in.uv);
+    let v = n;
+
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let h = importance_sample_ggx(hammersley(i), n, params.roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            sum = sum + textureSampleLevel(t_cube, s_cube, l, 0.0).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+    }
+
+    return vec4<f32>(sum / max(weight, 0.0001), 1.0);
+}
+
+// Smith with the k of image based lighting
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+// Scale in r and bias in g of f0, n·v along u and roughness along v
+@fragment
+fn fs_brdf(in: VertexOutput) -> @location(0) vec4<f32> {
+    let n_dot_v = max(in.uv.x, 0.001);
+    let roughness = in.uv.y;
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let h = importance_sample_ggx(hammersley(i), n, roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            let visibility = geometry_smith(n_dot_v, n_dot_l, roughness) * v_dot_h / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fresnel) * visibility;
+            bias = bias + fresnel * visibility;
+        }
+    }
+
+    return vec4<f32>(scale / f32(SAMPLE_COUNT), bias / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
@@ -0,0 +1,330 @@
+// Vertex shader
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+struct Light {
+    position: vec3<f32>,
+    // 0 point, 1 directional, 2 spot
+    kind: u32,
+    direction: vec3<f32>,
+    // 0 for lights without range
+    range: f32,
+    color: vec3<f32>,
+    intensity: f32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    shadow_matrix: mat4x4<f32>,
+    // -1 for lights without shadow
+    shadow_layer: i32,
+    shadow_bias: f32,
+    // Part of the layer the light renders into
+    shadow_scale: f32,
+    shadow_pcf_radius: i32,
+}
+
+// Same as MAX_LIGHTS of light.rs
+struct Lights {
+    lights: array<Light, 16>,
+    count: u32,
+    // 0 without an environment
+    environment_intensity: f32,
+    environment_mip_count: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(2)
+var s_shadow: sampler_comparison;
+@group(2) @binding(3)
+var t_irradiance: texture_cube<f32>;
+@group(2) @binding(4)
+var t_prefiltered: texture_cube<f32>;
+// Scale in r and bias in g of f0, by n·v and roughness
+@group(2) @binding(5)
+var t_brdf_lut: texture_2d<f32>;
+@group(2) @binding(6)
+var s_environment: sampler;
+
+// Same as MAX_JOINTS of skeleton.rs
+struct Skin {
+    joint_matrices: array<mat4x4<f32>, 128>,
+}
+
+@group(3) @binding(0)
+var<uniform> skin: Skin;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+    @location(9) normal_matrix_0: vec3<f32>,
+    @location(10) normal_matrix_1: vec3<f32>,
+    @location(11) normal_matrix_2: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) world_tangent: vec3<f32>,
+    @location(4) world_bitangent: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    // Blend the joint matrices, vertices without weights are not skinned
+    var skin_matrix = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    if (dot(model.weights, vec4<f32>(1.0)) > 0.0) {
+        skin_matrix = skin.joint_matrices[min(model.joints.x, 127u)] * model.weights.x
+            + skin.joint_matrices[min(model.joints.y, 127u)] * model.weights.y
+            + skin.joint_matrices[min(model.joints.z, 127u)] * model.weights.z
+            + skin.joint_matrices[min(model.joints.w, 127u)] * model.weights.w;
+    }
+    // Good enough for joints without non uniform scale
+    let skin_normal_matrix = mat3x3<f32>(
+        skin_matrix[0].xyz,
+        skin_matrix[1].xyz,
+        skin_matrix[2].xyz,
+    );
+
+    let world_position = model_matrix * skin_matrix * vec4<f32>(model.position, 1.0);
+
+    // Lights are summed up in world space, the fragment shader builds the tangent matrix
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.tex_coords = model.tex_coords;
+    out.world_position = world_position.xyz;
+    out.world_normal = normalize(normal_matrix * skin_normal_matrix * model.normal);
+    out.world_tangent = normalize(normal_matrix * skin_normal_matrix * model.tangent);
+    out.world_bitangent = normalize(normal_matrix * skin_normal_matrix * model.bitangent);
+    return out;
+}
+
+// Direction towards a light and the light arriving from it
+struct Incoming {
+    direction: vec3<f32>,
+    radiance: vec3<f32>,
+    // 0 in the shadow, 1 in the light
+    shadow: f32,
+}
+
+// Percentage closer filtered lookup of the shadow map
+fn shadow(light: Light, world_position: vec3<f32>) -> f32 {
+    if (light.shadow_layer < 0) {
+        return 1.0;
+    }
+
+    let clip = light.shadow_matrix * vec4<f32>(world_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    // Outside of what the light sees
+    if (clip.w <= 0.0 || ndc.z > 1.0 || abs(ndc.x) > 1.0 || abs(ndc.y) > 1.0) {
+        return 1.0;
+    }
+
+    let uv = (ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * light.shadow_scale;
+    let texel = 1.0 / vec2<f32>(textureDimensions(t_shadow));
+    let depth = ndc.z - light.shadow_bias;
+
+    var lit = 0.0;
+    var samples = 0.0;
+    for (var y = -light.shadow_pcf_radius; y <= light.shadow_pcf_radius; y = y + 1) {
+        for (var x = -light.shadow_pcf_radius; x <= light.shadow_pcf_radius; x = x + 1) {
+            let offset = vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, light.shadow_layer, depth);
+            samples = samples + 1.0;
+        }
+    }
+
+    return lit / samples;
+}
+
+fn incoming(light: Light, world_position: vec3<f32>) -> Incoming {
+    var out: Incoming;
+    out.radiance = light.color * light.intensity;
+    out.shadow = shadow(light, world_position);
+
+    if (light.kind == 1u) {
+        out.direction = -light.direction;
+        return out;
+    }
+
+    let to_light = light.position - world_position;
+    let distance = length(to_light);
+    out.direction = to_light / distance;
+
+    // Smooth window of KHR_lights_punctual, reaching 0 at the range
+    if (light.range > 0.0) {
+        let falloff = clamp(1.0 - pow(distance / light.range, 4.0), 0.0, 1.0);
+        out.radiance = out.radiance * falloff * falloff;
+    }
+    if (light.kind == 2u) {
+        let cos_angle = dot(light.direction, -out.direction);
+        out.radiance = out.radiance
+            * smoothstep(light.outer_cone_cos, light.inner_cone_cos, cos_angle);
+    }
+
+    return out;
+}
+
+// Fragment shader
+
+struct Factors {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+}
+
+@group(0) @binding(0)
+var<uniform> factors: Factors;
+@group(0) @binding(1)
+var s_material: sampler;
+@group(0) @binding(2)
+var t_base_color: texture_2d<f32>;
+// Roughness in g, metallic in b
+@group(0) @binding(3)
+var t_metallic_roughness: texture_2d<f32>;
+@group(0) @binding(4)
+var t_normal: texture_2d<f32>;
+// Occlusion in r
+@group(0) @binding(5)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(6)
+var t_emissive: texture_2d<f32>;
+
+let PI: f32 = 3.14159265359;
+
+// Trowbridge-Reitz GGX normal distribution
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let alpha2 = alpha * alpha;
+    let denom = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * denom * denom);
+}
+
+// Smith with Schlick-GGX for both directions
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    let g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return g_v * g_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Rough surfaces reflect less at grazing angles, for light from every direction
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0)
+        * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base_color = textureSample(t_base_color, s_material, in.tex_coords) * factors.base_color;
+    let metallic_roughness = textureSample(t_metallic_roughness, s_material, in.tex_coords);
+    let metallic = clamp(metallic_roughness.b * factors.metallic, 0.0, 1.0);
+    // Fully smooth surfaces make the highlight vanish
+    let roughness = clamp(metallic_roughness.g * factors.roughness, 0.04, 1.0);
+    let occlusion = mix(
+        1.0,
+        textureSample(t_occlusion, s_material, in.tex_coords).r,
+        factors.occlusion_strength,
+    );
+    let emissive = textureSample(t_emissive, s_material, in.tex_coords).rgb * factors.emissive;
+
+    let object_normal = textureSample(t_normal, s_material, in.tex_coords).xyz * 2.0 - 1.0;
+    let tangent_matrix = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    let n = normalize(tangent_matrix * (object_normal * vec3<f32>(factors.normal_scale, factors.normal_scale, 1.0)));
+    let v = normalize(camera.view_pos.xyz - in.world_position);
+    let n_dot_v = max(dot(n, v), 0.0001);
+
+    // Dielectrics reflect 4%, metals reflect their color
+    let f0 = mix(vec3<f32>(0.04), base_color.rgb, metallic);
+
+    var color = emissive;
+    // The environment replaces the ambient light of every light
+    var ambient_strength = 0.03;
+    if (lights.environment_intensity > 0.0) {
+        ambient_strength = 0.0;
+
+        let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+        let k_d = (1.0 - f) * (1.0 - metallic);
+        let irradiance = textureSampleLevel(t_irradiance, s_environment, n, 0.0).rgb;
+        // Rougher surfaces reflect blurrier mips
+        let lod = roughness * (lights.environment_mip_count - 1.0);
+        let prefiltered = textureSampleLevel(t_prefiltered, s_environment, reflect(-v, n), lod).rgb;
+        let brdf = textureSampleLevel(t_brdf_lut, s_environment, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+
+        color = color + (k_d * irradiance * base_color.rgb + prefiltered * (f * brdf.x + brdf.y))
+            * occlusion * lights.environment_intensity;
+    }
+    for (var i = 0u; i < min(lights.count, 16u); i = i + 1u) {
+        let light = incoming(lights.lights[i], in.world_position);
+        let l = light.direction;
+        let h = normalize(v + l);
+        let n_dot_l = max(dot(n, l), 0.0);
+        let n_dot_h = max(dot(n, h), 0.0);
+
+        let f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+        let d = distribution_ggx(n_dot_h, roughness * roughness);
+        let g = geometry_smith(n_dot_v, n_dot_l, roughness);
+        let specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+
+        // Metals have no diffuse part
+        let k_d = (1.0 - f) * (1.0 - metallic);
+        let direct = (k_d * base_color.rgb / PI + specular) * light.radiance * n_dot_l * light.shadow;
+        let ambient = ambient_strength * light.radiance * base_color.rgb * occlusion;
+
+        color = color + direct + ambient;
+    }
+
+    return vec4<f32>(color, base_color.a);
+}
@@ -9,10 +9,28 @@ var<uniform> camera: Camera;
 
 struct Light {
     position: vec3<f32>,
+    // 0 point, 1 directional, 2 spot
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
     color: vec3<f32>,
+    intensity: f32,
+    inner_cone_cos: f32,
+    outer_cone_cos: f32,
+    shadow_matrix: mat4x4<f32>,
+    shadow_layer: i32,
+    shadow_bias: f32,
+    shadow_scale: f32,
+    shadow_pcf_radius: i32,
+}
+
+// Same as MAX_LIGHTS of light.rs
+struct Lights {
+    lights: array<Light, 16>,
+    count: u32,
 }
 @group(1) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
 
 struct VertexInput {
     @location(0) position: vec3<f32>,
@@ -23,13 +41,29 @@ struct VertexOutput {
     @location(0) color: vec3<f32>,
 };
 
+// One instance per light, point lights are cubes,
+// the others are bars pointing where they shine
 @vertex
 fn vs_main(
     model: VertexInput,
+    @builtin(instance_index) instance: u32,
 ) -> VertexOutput {
-    let scale = 0.25;
+    let light = lights.lights[instance];
+
+    var position = model.position * 0.25;
+    if (light.kind != 0u) {
+        // Any vector not parallel to the direction does
+        var up = vec3<f32>(0.0, 1.0, 0.0);
+        if (abs(light.direction.y) > 0.99) {
+            up = vec3<f32>(1.0, 0.0, 0.0);
+        }
+        let x = normalize(cross(up, light.direction));
+        let y = cross(light.direction, x);
+        position = mat3x3<f32>(x, y, light.direction) * (model.position * vec3<f32>(0.1, 0.1, 0.5));
+    }
+
     var out: VertexOutput;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position * scale + light.position, 1.0);
+    out.clip_position = camera.view_proj * vec4<f32>(position + light.position, 1.0);
     out.color = light.color;
     return out;
 }
@@ -0,0 +1,66 @@
+// Vertex shader
+
+// View projection of the light
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Same as MAX_JOINTS of skeleton.rs
+struct Skin {
+    joint_matrices: array<mat4x4<f32>, 128>,
+}
+
+@group(1) @binding(0)
+var<uniform> skin: Skin;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(12) joints: vec4<u32>,
+    @location(13) weights: vec4<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+
+    // Same skinning as bp.wgsl
+    var skin_matrix = mat4x4<f32>(
+        vec4<f32>(1.0, 0.0, 0.0, 0.0),
+        vec4<f32>(0.0, 1.0, 0.0, 0.0),
+        vec4<f32>(0.0, 0.0, 1.0, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    if (dot(model.weights, vec4<f32>(1.0)) > 0.0) {
+        skin_matrix = skin.joint_matrices[min(model.joints.x, 127u)] * model.weights.x
+            + skin.joint_matrices[min(model.joints.y, 127u)] * model.weights.y
+            + skin.joint_matrices[min(model.joints.z, 127u)] * model.weights.z
+            + skin.joint_matrices[min(model.joints.w, 127u)] * model.weights.w;
+    }
+
+    return camera.view_proj * model_matrix * skin_matrix * vec4<f32>(model.position, 1.0);
+}
+
+// Fragment shader
+
+// Depth only
+@fragment
+fn fs_main() {}
@@ -0,0 +1,153 @@
+// Screen space ambient occlusion: points of the view space rebuilt from the depth
+// look around their hemisphere for what hides them, then the frame is darkened by the blurred result
+
+struct Ssao {
+    // Projection the scene was drawn with and its inverse
+    projection: mat4x4<f32>,
+    inverse_projection: mat4x4<f32>,
+    radius: f32,
+    intensity: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> ssao: Ssao;
+// Bound as a float texture, GLSL only compares the texels of depth textures
+@group(0) @binding(1)
+var t_depth: texture_2d<f32>;
+@group(0) @binding(2)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(3)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(4)
+var s_frame: sampler;
+
+let PI: f32 = 3.14159265359;
+let SAMPLE_COUNT: i32 = 16;
+// Side of the tile the samples are rotated over, blurred away afterwards
+let NOISE_SIZE: i32 = 4;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0, 0 at the top left of the target
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+fn load_depth(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    return textureLoad(t_depth, clamp(pixel, vec2<i32>(0), size - vec2<i32>(1)), 0).r;
+}
+
+// Point of the view space drawn at `pixel`
+fn view_position(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<f32>(textureDimensions(t_depth));
+    let uv = (vec2<f32>(pixel) + vec2<f32>(0.5)) / size;
+    let ndc = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, load_depth(pixel), 1.0);
+    let position = ssao.inverse_projection * ndc;
+    return position.xyz / position.w;
+}
+
+// Normal of the surface from the nearer neighbours on each axis, so edges don't bend it
+fn view_normal(pixel: vec2<i32>, center: vec3<f32>) -> vec3<f32> {
+    let left = center - view_position(pixel + vec2<i32>(-1, 0));
+    let right = view_position(pixel + vec2<i32>(1, 0)) - center;
+    let up = center - view_position(pixel + vec2<i32>(0, -1));
+    let down = view_position(pixel + vec2<i32>(0, 1)) - center;
+
+    var dx = right;
+    if (abs(left.z) < abs(right.z)) {
+        dx = left;
+    }
+    var dy = down;
+    if (abs(up.z) < abs(down.z)) {
+        dy = up;
+    }
+
+    let normal = normalize(cross(dy, dx));
+    if (dot(normal, center) > 0.0) {
+        return -normal;
+    }
+    return normal;
+}
+
+@fragment
+fn fs_occlusion(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+
+    // Nothing was drawn, the background stays lit
+    if (load_depth(pixel) >= 1.0) {
+        return vec4<f32>(1.0);
+    }
+
+    let center = view_position(pixel);
+    let normal = view_normal(pixel, center);
+
+    // Basis around the normal, turned by an angle repeating every NOISE_SIZE pixels
+    let tile = pixel % vec2<i32>(NOISE_SIZE);
+    let angle = f32(tile.x * NOISE_SIZE + tile.y) / f32(NOISE_SIZE * NOISE_SIZE) * 2.0 * PI;
+    let random = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let tangent = normalize(random - normal * dot(random, normal) + vec3<f32>(0.0, 0.0, 0.001));
+    let bitangent = cross(normal, tangent);
+
+    let bias = 0.025 * ssao.radius;
+    var occlusion = 0.0;
+    for (var i = 0; i < SAMPLE_COUNT; i = i + 1) {
+        // Spiral over the hemisphere, the samples gather towards the point
+        let t = (f32(i) + 0.5) / f32(SAMPLE_COUNT);
+        let cos_theta = sqrt(1.0 - t);
+        let sin_theta = sqrt(t);
+        let phi = f32(i) * 2.39996323;
+        let direction = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+        let scale = mix(0.1, 1.0, t * t);
+        let offset = (tangent * direction.x + bitangent * direction.y + normal * direction.z)
+            * scale * ssao.radius;
+        let probe = center + offset;
+
+        let clip = ssao.projection * vec4<f32>(probe, 1.0);
+        let ndc = clip.xy / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        if (clip.w <= 0.0 || any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+            continue;
+        }
+
+        let size = vec2<f32>(textureDimensions(t_depth));
+        let scene = view_position(vec2<i32>(uv * size));
+        // What is far in front of the point doesn't hide it
+        let range = smoothstep(0.0, 1.0, ssao.radius / abs(center.z - scene.z));
+        if (scene.z >= probe.z + bias) {
+            occlusion = occlusion + range;
+        }
+    }
+
+    return vec4<f32>(1.0 - occlusion / f32(SAMPLE_COUNT));
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<i32>(textureDimensions(t_occlusion));
+
+    // Box blur over one tile of the rotations
+    var occlusion = 0.0;
+    for (var y = -NOISE_SIZE / 2; y < NOISE_SIZE / 2; y = y + 1) {
+        for (var x = -NOISE_SIZE / 2; x < NOISE_SIZE / 2; x = x + 1) {
+            let coords = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - vec2<i32>(1));
+            occlusion = occlusion + textureLoad(t_occlusion, coords, 0).r;
+        }
+    }
+    occlusion = occlusion / f32(NOISE_SIZE * NOISE_SIZE);
+
+    let frame = textureSampleLevel(t_frame, s_frame, in.uv, 0.0);
+    return vec4<f32>(frame.rgb * pow(occlusion, ssao.intensity), frame.a);
+}
@@ -0,0 +1,57 @@
+// Maps the colors of the HDR frame into the range of the target
+
+struct Tonemap {
+    exposure: f32,
+    // 0 clamps, 1 Reinhard, 2 ACES
+    curve: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> tonemap: Tonemap;
+@group(0) @binding(1)
+var t_frame: texture_2d<f32>;
+@group(0) @binding(2)
+var s_frame: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    // 0, 0 at the top left of the target
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    return out;
+}
+
+// Narkowicz's fit of the filmic curve of ACES
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let frame = textureSampleLevel(t_frame, s_frame, in.uv, 0.0);
+    let color = max(frame.rgb * tonemap.exposure, vec3<f32>(0.0));
+
+    var mapped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    if (tonemap.curve == 1u) {
+        mapped = color / (color + vec3<f32>(1.0));
+    } else if (tonemap.curve == 2u) {
+        mapped = aces(color);
+    }
+
+    return vec4<f32>(mapped, clamp(frame.a, 0.0, 1.0));
+}